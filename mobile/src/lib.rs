@@ -16,7 +16,7 @@ fn main() {
                     ..default()
                 })
                 .set(ImagePlugin::default_nearest()),
-            GamePlugin,
+            GamePlugin::default(),
         ))
         .run()
 }
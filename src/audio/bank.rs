@@ -0,0 +1,111 @@
+use bevy::utils::thiserror;
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+    utils::{BoxedFuture, HashMap},
+};
+use bevy_kira_audio::AudioSource;
+use futures_lite::AsyncReadExt;
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Deserialize)]
+struct RonSoundBank {
+    #[serde(default)]
+    music: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    sounds: HashMap<String, RonSound>,
+}
+
+#[derive(Deserialize)]
+struct RonSound {
+    sources: Vec<String>,
+    #[serde(default = "full_volume")]
+    volume: f32,
+}
+fn full_volume() -> f32 {
+    1.
+}
+
+/// Music and sound effects of the game, paths in the file are relative to it
+#[derive(Asset, TypePath)]
+pub struct SoundBank {
+    /// Playlists by the name of a game state
+    pub music: HashMap<String, Vec<Handle<AudioSource>>>,
+    pub sounds: HashMap<String, Sound>,
+}
+
+pub struct Sound {
+    /// Variants of the sound, one is picked for every play
+    pub sources: Vec<Handle<AudioSource>>,
+    pub volume: f32,
+}
+impl Sound {
+    pub fn pick(&self) -> Option<&Handle<AudioSource>> {
+        if self.sources.is_empty() {
+            return None;
+        }
+        self.sources
+            .get(rand::random::<usize>() % self.sources.len())
+    }
+}
+
+#[derive(Default)]
+pub struct SoundBankLoader;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum SoundBankLoaderError {
+    #[error("Could not load sound bank: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse RON: {0}")]
+    RonSpannedError(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for SoundBankLoader {
+    type Asset = SoundBank;
+    type Settings = ();
+    type Error = SoundBankLoaderError;
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let dir = load_context
+                .path()
+                .parent()
+                .map(|dir| dir.to_path_buf())
+                .unwrap_or_default();
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let bank = ron::de::from_bytes::<RonSoundBank>(&bytes)?;
+
+            // Sounds are dependencies, so the bank is loaded when all of them are
+            let mut load = |source: &String| load_context.load(dir.join(source));
+            let music = bank
+                .music
+                .iter()
+                .map(|(state, tracks)| (state.clone(), tracks.iter().map(&mut load).collect()))
+                .collect();
+            let sounds = bank
+                .sounds
+                .iter()
+                .map(|(name, sound)| {
+                    let sound = Sound {
+                        sources: sound.sources.iter().map(&mut load).collect(),
+                        volume: sound.volume,
+                    };
+                    (name.clone(), sound)
+                })
+                .collect();
+            Ok(SoundBank { music, sounds })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bank.ron"]
+    }
+}
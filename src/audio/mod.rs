@@ -0,0 +1,214 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::*;
+
+use crate::{player::PlayerCamera, settings, GameState, LoadProcess, ASSET_TIMEOUT};
+
+mod bank;
+pub use bank::*;
+
+const SOUND_BANK: &str = "Audio/sounds.bank.ron";
+const MUSIC_FADE: Duration = Duration::from_secs(1);
+// Positional sounds are silent farther than this from the camera, in pixels
+const HEARING_DISTANCE: f32 = 600.;
+
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(AudioPlugin)
+            .add_audio_channel::<Music>()
+            .add_audio_channel::<Sfx>()
+            .init_asset::<SoundBank>()
+            .init_asset_loader::<SoundBankLoader>()
+            .add_event::<PlaySfx>()
+            .init_resource::<Sounds>()
+            .init_resource::<Playlist>()
+            .init_resource::<PositionalSounds>()
+            .add_systems(OnEnter(GameState::Loading), load_sounds)
+            .add_systems(
+                Update,
+                (
+                    update_playlist,
+                    apply_music_volume.run_if(resource_changed::<settings::Volume>()),
+                    (play_sfx, update_positional).chain(),
+                ),
+            );
+    }
+}
+
+#[derive(Resource)]
+struct Music;
+
+#[derive(Resource)]
+struct Sfx;
+
+/// Plays a sound of the sound bank, a sound with a position fades with distance from the camera.
+/// Mining and working machines are heard with `PlaySfx::at("mine", position)` and
+/// `PlaySfx::at("machine", position)` at the place of the block or the machine
+#[derive(Event)]
+pub struct PlaySfx {
+    pub sound: &'static str,
+    pub position: Option<Vec2>,
+}
+impl PlaySfx {
+    /// Sound heard from a place in the world
+    pub fn at(sound: &'static str, position: Vec2) -> Self {
+        Self {
+            sound,
+            position: Some(position),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct Sounds(Handle<SoundBank>);
+
+/// Music which is playing now
+#[derive(Resource, Default)]
+struct Playlist {
+    state: Option<String>,
+    /// Index of the next track
+    next: usize,
+    track: Option<Handle<AudioInstance>>,
+}
+
+struct PositionalSound {
+    instance: Handle<AudioInstance>,
+    position: Vec2,
+    volume: f32,
+}
+
+#[derive(Resource, Default)]
+struct PositionalSounds(Vec<PositionalSound>);
+
+// Volume and panning of a sound heard from `listener`
+fn spatial(listener: Vec2, position: Vec2) -> (f32, f32) {
+    let path = position - listener;
+    let gain = (1. - path.length() / HEARING_DISTANCE)
+        .clamp(0., 1.)
+        .powi(2);
+    let panning = (0.5 + path.x / HEARING_DISTANCE / 2.).clamp(0., 1.);
+    (gain, panning)
+}
+
+// logic
+fn load_sounds(
+    mut sounds: ResMut<Sounds>,
+    asset_server: Res<AssetServer>,
+    mut load: ResMut<LoadProcess>,
+) {
+    sounds.0 = asset_server.load(SOUND_BANK);
+    load.add("audio")
+        .track(sounds.0.clone())
+        .timeout(ASSET_TIMEOUT);
+}
+
+fn update_playlist(
+    state: Res<State<GameState>>,
+    sounds: Res<Sounds>,
+    banks: Res<Assets<SoundBank>>,
+    music: Res<AudioChannel<Music>>,
+    mut playlist: ResMut<Playlist>,
+) {
+    let Some(bank) = banks.get(&sounds.0) else {
+        return;
+    };
+    // States without a playlist keep the music, so the pause menu doesn't restart it
+    let key = format!("{:?}", state.get());
+    if bank.music.contains_key(&key) && playlist.state.as_ref() != Some(&key) {
+        music.stop().fade_out(AudioTween::linear(MUSIC_FADE));
+        *playlist = Playlist {
+            state: Some(key),
+            ..Default::default()
+        };
+    }
+    let Some(tracks) = playlist.state.as_ref().and_then(|key| bank.music.get(key)) else {
+        return;
+    };
+    if tracks.is_empty() {
+        return;
+    }
+    let finished = match &playlist.track {
+        Some(track) => music.state(track) == PlaybackState::Stopped,
+        None => true,
+    };
+    if finished {
+        let track = tracks[playlist.next % tracks.len()].clone();
+        playlist.track = Some(music.play(track).linear_fade_in(MUSIC_FADE).handle());
+        playlist.next += 1;
+    }
+}
+
+fn apply_music_volume(volume: Res<settings::Volume>, music: Res<AudioChannel<Music>>) {
+    music.set_volume((volume.master * volume.music) as f64);
+}
+
+fn play_sfx(
+    mut events: EventReader<PlaySfx>,
+    sounds: Res<Sounds>,
+    banks: Res<Assets<SoundBank>>,
+    sfx: Res<AudioChannel<Sfx>>,
+    volume: Res<settings::Volume>,
+    listener: Query<&GlobalTransform, With<PlayerCamera>>,
+    mut positional: ResMut<PositionalSounds>,
+) {
+    let Some(bank) = banks.get(&sounds.0) else {
+        events.clear();
+        return;
+    };
+    let listener = listener
+        .get_single()
+        .map_or(Vec2::ZERO, |transform| transform.translation().truncate());
+    for ev in events.read() {
+        let Some(sound) = bank.sounds.get(ev.sound) else {
+            warn!("Sound {} is not in the sound bank", ev.sound);
+            continue;
+        };
+        let Some(source) = sound.pick() else {
+            continue;
+        };
+        let (gain, panning) = ev
+            .position
+            .map_or((1., 0.5), |position| spatial(listener, position));
+        let instance = sfx
+            .play(source.clone())
+            .with_volume((volume.master * volume.sfx * sound.volume * gain) as f64)
+            .with_panning(panning as f64)
+            .handle();
+        if let Some(position) = ev.position {
+            positional.0.push(PositionalSound {
+                instance,
+                position,
+                volume: sound.volume,
+            });
+        }
+    }
+}
+
+// Follows the camera and the volume settings while positional sounds play
+fn update_positional(
+    mut positional: ResMut<PositionalSounds>,
+    sfx: Res<AudioChannel<Sfx>>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+    volume: Res<settings::Volume>,
+    listener: Query<&GlobalTransform, With<PlayerCamera>>,
+) {
+    positional
+        .0
+        .retain(|sound| sfx.state(&sound.instance) != PlaybackState::Stopped);
+    let Ok(listener) = listener.get_single() else {
+        return;
+    };
+    let listener = listener.translation().truncate();
+    for sound in positional.0.iter() {
+        let Some(instance) = instances.get_mut(&sound.instance) else {
+            continue;
+        };
+        let (gain, panning) = spatial(listener, sound.position);
+        let volume = volume.master * volume.sfx * sound.volume * gain;
+        instance.set_volume(volume as f64, AudioTween::default());
+        instance.set_panning(panning as f64, AudioTween::default());
+    }
+}
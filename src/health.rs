@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+
+use crate::simulation::{Simulation, TickRate};
+
+// How long an entity ignores damage after being hit, in seconds
+const INVULNERABILITY_TIME: f32 = 1.;
+// How fast the sprite blinks while invulnerable, in blinks per second
+const BLINK_RATE: f32 = 8.;
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Health>()
+            .add_event::<DamageEvent>()
+            .add_event::<HealEvent>()
+            .add_event::<DeathEvent>()
+            .add_systems(
+                FixedUpdate,
+                (update_invulnerability, apply_damage, apply_heal)
+                    .chain()
+                    .in_set(HealthSystems)
+                    .in_set(Simulation),
+            );
+    }
+}
+
+/// Damage and healing of a tick, deaths are sent from here
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HealthSystems;
+
+/// Health in half hearts: one `hp` icon is 2 points, a `half_hp` icon is 1
+#[derive(Component, Reflect)]
+pub struct Health {
+    pub max: u32,
+    pub current: u32,
+}
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self { max, current: max }
+    }
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+}
+
+/// Entity ignores damage for the remaining ticks
+#[derive(Component)]
+pub struct Invulnerable(pub u32);
+impl Invulnerable {
+    pub fn new(rate: &TickRate) -> Self {
+        Self(rate.ticks(INVULNERABILITY_TIME))
+    }
+}
+
+/// Marks entity which health reached zero
+#[derive(Component)]
+pub struct Dead;
+
+#[derive(Event)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: u32,
+}
+
+#[derive(Event)]
+pub struct HealEvent {
+    pub target: Entity,
+    pub amount: u32,
+}
+
+#[derive(Event)]
+pub struct DeathEvent(pub Entity);
+
+// logic
+fn apply_damage(
+    mut commands: Commands,
+    mut events: EventReader<DamageEvent>,
+    mut query: Query<&mut Health, (Without<Invulnerable>, Without<Dead>)>,
+    mut deaths: EventWriter<DeathEvent>,
+    rate: Res<TickRate>,
+) {
+    for ev in events.read() {
+        let Ok(mut health) = query.get_mut(ev.target) else {
+            continue;
+        };
+        if health.is_dead() {
+            continue;
+        }
+        health.current = health.current.saturating_sub(ev.amount);
+        if health.is_dead() {
+            commands.entity(ev.target).insert(Dead);
+            deaths.send(DeathEvent(ev.target));
+        } else {
+            commands.entity(ev.target).insert(Invulnerable::new(&rate));
+        }
+    }
+}
+
+fn apply_heal(mut events: EventReader<HealEvent>, mut query: Query<&mut Health, Without<Dead>>) {
+    for ev in events.read() {
+        if let Ok(mut health) = query.get_mut(ev.target) {
+            health.current = health.current.saturating_add(ev.amount).min(health.max);
+        }
+    }
+}
+
+fn update_invulnerability(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Invulnerable, Option<&mut TextureAtlasSprite>)>,
+    rate: Res<TickRate>,
+) {
+    for (entity, mut invulnerable, sprite) in query.iter_mut() {
+        invulnerable.0 = invulnerable.0.saturating_sub(1);
+        let finished = invulnerable.0 == 0;
+        if let Some(mut sprite) = sprite {
+            let left = invulnerable.0 as f64 / rate.0;
+            let blink = (left * BLINK_RATE as f64) as u32 % 2 == 1;
+            sprite
+                .color
+                .set_a(if blink && !finished { 0.3 } else { 1. });
+        }
+        if finished {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
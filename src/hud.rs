@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+
+use crate::{health::Health, player::Player, resources::*, GameState};
+
+const HUD_TSET: &str = "Graphics/items.tset.ron";
+const HEART_SIZE: f32 = 32.;
+// Tint of a heart which is lost
+const EMPTY_HEART: Color = Color::rgba(0.1, 0.1, 0.1, 0.6);
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_tset_collection::<HudAssets>()
+            .register_type::<HudAssets>()
+            .add_systems(Update, setup_hud.in_set(crate::GameSetup))
+            .add_systems(Update, update_hearts.run_if(in_state(GameState::Playing)));
+    }
+}
+
+#[derive(Component)]
+struct HeartBar;
+
+// logic
+fn setup_hud(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.),
+                left: Val::Px(8.),
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(4.),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Name::new("Heart bar"),
+        HeartBar,
+        crate::InGame,
+    ));
+}
+
+fn update_hearts(
+    mut commands: Commands,
+    bar: Query<Entity, With<HeartBar>>,
+    player: Query<&Health, (With<Player>, Changed<Health>)>,
+    assets: Res<HudAssets>,
+) {
+    let Ok(health) = player.get_single() else {
+        return;
+    };
+    let bar = bar.single();
+    commands.entity(bar).despawn_descendants();
+    commands.entity(bar).with_children(|parent| {
+        for heart in 0..health.max.div_ceil(2) {
+            let points = health.current.saturating_sub(heart * 2).min(2);
+            let (tile, color) = match points {
+                0 => ("hp", EMPTY_HEART),
+                1 => ("half_hp", Color::WHITE),
+                _ => ("hp", Color::WHITE),
+            };
+            parent.spawn((
+                AtlasImageBundle {
+                    style: Style {
+                        width: Val::Px(HEART_SIZE),
+                        height: Val::Px(HEART_SIZE),
+                        ..Default::default()
+                    },
+                    background_color: color.into(),
+                    ..Default::default()
+                },
+                TSetManager::new(assets.tileset.clone(), tile, TSetTile::Single),
+            ));
+        }
+    });
+}
+
+// HUD assets
+#[derive(Resource, Reflect, Default)]
+struct HudAssets {
+    tileset: Handle<TextureSetAsset>,
+}
+impl TSetCollection for HudAssets {
+    const STEP: &'static str = "hud";
+    fn tsets(&mut self) -> Vec<(&mut Handle<TextureSetAsset>, &'static str)> {
+        vec![(&mut self.tileset, HUD_TSET)]
+    }
+}
@@ -1,24 +1,32 @@
 #![allow(clippy::type_complexity)]
 
+mod audio;
+pub mod health;
+mod hud;
 mod load;
-mod map;
-mod player;
+pub mod map;
+mod menu;
+pub mod player;
 mod plugins;
-mod resources;
-mod settings;
+pub mod replay;
+pub mod resources;
+pub mod settings;
+pub mod simulation;
+pub mod sorting;
+mod touch;
 
 use load::*;
 
 use bevy::app::App;
 #[cfg(debug_assertions)]
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
-use bevy::prelude::*;
+use bevy::{input::InputPlugin, prelude::*};
 
 // This example game uses States to separate logic
 // See https://bevy-cheatbook.github.io/programming/states.html
 // Or https://github.com/bevyengine/bevy/blob/main/examples/ecs/state.rs
 #[derive(States, Default, Clone, Eq, PartialEq, Debug, Hash)]
-enum GameState {
+pub enum GameState {
     // During the loading State the LoadingPlugin will load our assets
     #[default]
     Loading,
@@ -26,28 +34,90 @@ enum GameState {
     Playing,
     // Here the menu is drawn and waiting for player interaction
     Menu,
+    // The game world is kept but frozen under the pause menu
+    Paused,
+    // Settings screen, opened from the main or the pause menu
+    Settings,
+    // Some assets failed to load, the failed steps are listed
+    LoadFailed,
 }
+
+/// Starts a new game: systems in [`GameSetup`] rebuild the world
+#[derive(Event)]
+pub struct NewGame {
+    /// Same seed gives the same world
+    pub seed: u64,
+}
+impl NewGame {
+    pub fn random() -> Self {
+        Self {
+            seed: rand::random(),
+        }
+    }
+}
+
+/// Systems that spawn the game world, they run once per [`NewGame`]
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GameSetup;
+
+/// Marks entities of the running game, they are despawned on [`NewGame`]
+#[derive(Component)]
+pub struct InGame;
+
+/// Number of simulation steps since the game started
 #[derive(Resource)]
-pub struct Tick(u64);
+pub struct Tick(pub u64);
 
-pub struct GamePlugin;
+pub struct GamePlugin {
+    /// Camera, sprites, UI, audio and settings files, without them only the simulation runs
+    pub render: bool,
+}
+impl Default for GamePlugin {
+    fn default() -> Self {
+        Self { render: true }
+    }
+}
+impl GamePlugin {
+    /// The game without presentation, for tests and servers running with `MinimalPlugins`
+    pub fn headless() -> Self {
+        Self { render: false }
+    }
+}
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
+        // `MinimalPlugins` lack the engine parts the simulation uses
+        if !app.is_plugin_added::<AssetPlugin>() {
+            app.add_plugins(AssetPlugin::default());
+        }
+        if !app.is_plugin_added::<InputPlugin>() {
+            app.add_plugins(InputPlugin);
+        }
+        if !app.is_plugin_added::<TransformPlugin>() {
+            app.add_plugins((TransformPlugin, HierarchyPlugin));
+        }
+
         app.add_state::<GameState>()
-            .add_plugins(plugins::Plugins)
-            .add_systems(Startup, setup_camera)
+            .add_event::<NewGame>()
+            .configure_sets(Update, GameSetup.run_if(on_event::<NewGame>()))
+            .add_plugins(plugins::Plugins {
+                render: self.render,
+            })
             .insert_resource(Tick(0))
-            .add_systems(Update, update_tick.run_if(in_state(GameState::Playing)));
+            .add_systems(Update, reset_game.in_set(GameSetup));
 
-        #[cfg(debug_assertions)]
-        {
+        if self.render {
+            app.add_systems(Startup, setup_camera);
+            #[cfg(debug_assertions)]
             app.add_plugins((FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin::default()));
         }
     }
 }
-fn update_tick(mut tick: ResMut<Tick>) {
-    tick.0 += 1;
+fn reset_game(mut commands: Commands, query: Query<Entity, With<InGame>>, mut tick: ResMut<Tick>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    tick.0 = 0;
 }
 
 fn setup_camera(mut commands: Commands) {
@@ -1,84 +1,425 @@
-use crate::GameState;
-use bevy::{prelude::*, utils::HashMap};
+use crate::{
+    menu::{MenuAction, BUTTON_COLOR, MENU_BACKGROUND, TEXT_COLOR},
+    settings::controls::ControlBinds,
+    GameState,
+};
+use bevy::{asset::RecursiveDependencyLoadState, prelude::*, utils::HashMap};
 
-const FONT: &str = "fonts/FiraSans-Bold.ttf";
+pub const FONT: &str = "fonts/FiraSans-Bold.ttf";
+/// Time an asset step may take before loading is reported as failed, in seconds
+pub const ASSET_TIMEOUT: f32 = 30.;
+const FAILED_COLOR: Color = Color::rgb(0.9, 0.3, 0.3);
+const DONE_COLOR: Color = Color::rgb(0.4, 0.8, 0.4);
+const PENDING_COLOR: Color = Color::rgb(0.5, 0.5, 0.5);
+const BAR_WIDTH: f32 = 400.;
+const BAR_HEIGHT: f32 = 24.;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum StepState {
+    Pending,
+    Loading,
+    Done,
+    Failed(String),
+}
+
+pub struct LoadStep {
+    state: StepState,
+    weight: f32,
+    timeout: Option<f32>,
+    /// Seconds since the step was added
+    elapsed: f32,
+    /// Assets the step waits for, with their dependencies
+    handles: Vec<UntypedHandle>,
+}
+impl LoadStep {
+    /// The step is done when all tracked assets and their dependencies are loaded
+    pub fn track<A: Asset>(&mut self, handle: Handle<A>) -> &mut Self {
+        self.handles.push(handle.untyped());
+        self
+    }
+    /// Share of the progress bar, 1 by default
+    pub fn weight(&mut self, weight: f32) -> &mut Self {
+        self.weight = weight;
+        self
+    }
+    /// The step fails if it is not done in `seconds`
+    pub fn timeout(&mut self, seconds: f32) -> &mut Self {
+        self.timeout = Some(seconds);
+        self
+    }
+    fn is_finished(&self) -> bool {
+        matches!(self.state, StepState::Done | StepState::Failed(_))
+    }
+}
 
 #[derive(Resource)]
 pub struct LoadProcess {
-    steps: HashMap<String, bool>,
+    steps: HashMap<String, LoadStep>,
 }
 impl LoadProcess {
-    pub fn add(&mut self, step_name: &str) {
-        self.steps.insert(step_name.to_string(), false);
+    pub fn add(&mut self, step_name: &str) -> &mut LoadStep {
+        self.steps.entry(step_name.to_string()).or_insert(LoadStep {
+            state: StepState::Pending,
+            weight: 1.,
+            timeout: None,
+            elapsed: 0.,
+            handles: Vec::new(),
+        })
     }
+    /// Marks the step as started, finished steps are kept
+    pub fn start(&mut self, step_name: &str) {
+        if let Some(step) = self.step_mut(step_name) {
+            if step.state == StepState::Pending {
+                step.state = StepState::Loading;
+            }
+        }
+    }
+    /// Marks the step as done
     pub fn set(&mut self, step_name: &str) {
-        *self.steps.get_mut(step_name).unwrap() = true;
+        self.finish(step_name, StepState::Done);
+    }
+    pub fn fail(&mut self, step_name: &str, reason: impl Into<String>) {
+        self.finish(step_name, StepState::Failed(reason.into()));
+    }
+    pub fn state(&self, step_name: &str) -> Option<&StepState> {
+        self.steps.get(step_name).map(|step| &step.state)
+    }
+    /// Weighted share of finished steps, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        let total: f32 = self.steps.values().map(|step| step.weight).sum();
+        if total <= 0. {
+            return 1.;
+        }
+        let finished: f32 = self
+            .steps
+            .values()
+            .filter(|step| step.is_finished())
+            .map(|step| step.weight)
+            .sum();
+        finished / total
     }
     fn new() -> Self {
         Self {
             steps: HashMap::new(),
         }
     }
+    fn finish(&mut self, step_name: &str, state: StepState) {
+        if let Some(step) = self.step_mut(step_name) {
+            // A timed out step which loads later is still reported as failed
+            if !step.is_finished() {
+                step.state = state;
+            }
+        }
+    }
+    fn step_mut(&mut self, step_name: &str) -> Option<&mut LoadStep> {
+        let step = self.steps.get_mut(step_name);
+        if step.is_none() {
+            warn!("Load step {step_name} is not added");
+        }
+        step
+    }
 }
 
+/// Steps which failed during the last loading, shown on the failure screen
+#[derive(Resource, Default)]
+struct LoadFailures(Vec<(String, String)>);
+
 pub struct LoadPlugin;
 
 impl Plugin for LoadPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(LoadProcess::new())
-            .add_systems(OnEnter(GameState::Loading), spawn_progress_bar)
+            .init_resource::<LoadFailures>()
             .add_systems(
                 Update,
-                check_load_process.run_if(in_state(GameState::Loading)),
+                (track_handles, check_load_process)
+                    .chain()
+                    .in_set(CheckLoad)
+                    .run_if(in_state(GameState::Loading)),
             )
+            .add_systems(OnExit(GameState::Loading), set_load_process);
+    }
+}
+
+/// Loading and failure screens
+pub struct LoadRenderPlugin;
+
+impl Plugin for LoadRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Loading), (spawn_progress_bar, load_font))
             .add_systems(
-                OnExit(GameState::Loading),
-                (set_load_process, despawn_progress_bar),
-            );
+                Update,
+                (
+                    update_progress_bar
+                        .after(CheckLoad)
+                        .run_if(in_state(GameState::Loading)),
+                    continue_after_failure.run_if(in_state(GameState::LoadFailed)),
+                ),
+            )
+            .add_systems(OnExit(GameState::Loading), despawn_progress_bar)
+            .add_systems(OnEnter(GameState::LoadFailed), spawn_failure_screen)
+            .add_systems(OnExit(GameState::LoadFailed), despawn_failure_screen);
     }
 }
 
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct CheckLoad;
+
+#[derive(Component)]
+struct LoadingScreen;
+
 #[derive(Component)]
 struct ProgressBar;
 
-fn spawn_progress_bar(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let font = asset_server.load(FONT);
+#[derive(Component)]
+struct StepLabels;
+
+#[derive(Component)]
+struct FailureScreen;
+
+fn spawn_progress_bar(mut commands: Commands) {
     commands
-        .spawn(TextBundle::from_section(
-            "0/0",
-            TextStyle {
-                font: font.clone(),
-                font_size: 32.,
-                color: Color::WHITE,
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.),
+                    ..Default::default()
+                },
+                ..Default::default()
             },
+            Name::new("Loading screen"),
+            LoadingScreen,
         ))
-        .insert(ProgressBar);
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(BAR_WIDTH),
+                        height: Val::Px(BAR_HEIGHT),
+                        ..Default::default()
+                    },
+                    background_color: BUTTON_COLOR.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.),
+                                height: Val::Percent(100.),
+                                ..Default::default()
+                            },
+                            background_color: DONE_COLOR.into(),
+                            ..Default::default()
+                        },
+                        ProgressBar,
+                    ));
+                });
+            parent.spawn((
+                TextBundle::default().with_text_alignment(TextAlignment::Center),
+                StepLabels,
+            ));
+        });
 }
-fn despawn_progress_bar(mut commands: Commands, query: Query<Entity, With<ProgressBar>>) {
-    let id = query.single();
-    commands.entity(id).despawn();
+fn despawn_progress_bar(mut commands: Commands, query: Query<Entity, With<LoadingScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn load_font(asset_server: Res<AssetServer>, mut process: ResMut<LoadProcess>) {
+    process
+        .add("font")
+        .track(asset_server.load::<Font>(FONT))
+        .timeout(ASSET_TIMEOUT);
+}
+
+// Finishes steps which track assets, nested assets like tset images are waited for too
+fn track_handles(mut process: ResMut<LoadProcess>, server: Res<AssetServer>) {
+    for step in process.steps.values_mut() {
+        if step.is_finished() || step.handles.is_empty() {
+            continue;
+        }
+        let mut loaded = true;
+        for handle in step.handles.iter() {
+            match server.get_recursive_dependency_load_state(handle.id()) {
+                Some(RecursiveDependencyLoadState::Loaded) => {}
+                Some(RecursiveDependencyLoadState::Failed) => {
+                    let path = handle
+                        .path()
+                        .map_or("asset".to_string(), |path| path.to_string());
+                    step.state = StepState::Failed(format!("{path} failed to load"));
+                    break;
+                }
+                _ => {
+                    loaded = false;
+                    step.state = StepState::Loading;
+                }
+            }
+        }
+        if loaded && !step.is_finished() {
+            step.state = StepState::Done;
+        }
+    }
 }
 
 fn check_load_process(
-    process: Res<LoadProcess>,
+    mut process: ResMut<LoadProcess>,
     mut state: ResMut<NextState<GameState>>,
-    mut query: Query<&mut Text, With<ProgressBar>>,
+    mut failures: ResMut<LoadFailures>,
+    time: Res<Time<Real>>,
 ) {
-    let end = process.steps.len();
-    let mut now = 0;
-
-    for (_, i) in process.steps.iter() {
-        if *i {
-            now += 1;
+    for (name, step) in process.steps.iter_mut() {
+        if step.is_finished() {
+            continue;
+        }
+        step.elapsed += time.delta_seconds();
+        if let Some(timeout) = step.timeout.filter(|timeout| step.elapsed > *timeout) {
+            warn!("Load step {name} timed out");
+            step.state = StepState::Failed(format!("timed out after {timeout}s"));
         }
     }
-    let mut text = query.single_mut();
-    text.sections[0].value = format!("{now}/{end}");
-    if now == end {
-        state.set(GameState::Playing);
-        info!("Loading is ended! {}", text.sections[0].value);
+
+    if !process.steps.values().all(LoadStep::is_finished) {
+        return;
+    }
+    failures.0 = process
+        .steps
+        .iter()
+        .filter_map(|(name, step)| match &step.state {
+            StepState::Failed(reason) => Some((name.clone(), reason.clone())),
+            _ => None,
+        })
+        .collect();
+    failures.0.sort();
+    if failures.0.is_empty() {
+        state.set(GameState::Menu);
+        info!("Loading is ended!");
+    } else {
+        state.set(GameState::LoadFailed);
+        warn!("Loading failed: {} steps", failures.0.len());
+    }
+}
+fn update_progress_bar(
+    process: Res<LoadProcess>,
+    asset_server: Res<AssetServer>,
+    mut bar: Query<&mut Style, With<ProgressBar>>,
+    mut labels: Query<&mut Text, With<StepLabels>>,
+) {
+    for mut style in bar.iter_mut() {
+        style.width = Val::Percent(process.progress() * 100.);
+    }
+    let font = asset_server.load(FONT);
+    let mut steps: Vec<_> = process.steps.iter().collect();
+    steps.sort_by_key(|(name, _)| name.as_str());
+    for mut text in labels.iter_mut() {
+        text.sections = steps
+            .iter()
+            .map(|(name, step)| {
+                let (state, color) = match &step.state {
+                    StepState::Pending => ("waiting", PENDING_COLOR),
+                    StepState::Loading => ("loading", TEXT_COLOR),
+                    StepState::Done => ("done", DONE_COLOR),
+                    StepState::Failed(_) => ("failed", FAILED_COLOR),
+                };
+                TextSection::new(
+                    format!("{name}: {state}\n"),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 24.,
+                        color,
+                    },
+                )
+            })
+            .collect();
     }
 }
 fn set_load_process(mut process: ResMut<LoadProcess>) {
     process.steps = LoadProcess::new().steps;
 }
+
+fn spawn_failure_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    failures: Res<LoadFailures>,
+) {
+    let font = asset_server.load(FONT);
+    let style = |font_size, color| TextStyle {
+        font: font.clone(),
+        font_size,
+        color,
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.),
+                    ..Default::default()
+                },
+                background_color: MENU_BACKGROUND.into(),
+                ..Default::default()
+            },
+            Name::new("Loading failed"),
+            FailureScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Loading failed",
+                style(48., TEXT_COLOR),
+            ));
+            for (name, reason) in failures.0.iter() {
+                parent.spawn(TextBundle::from_section(
+                    format!("{name}: {reason}"),
+                    style(24., FAILED_COLOR),
+                ));
+            }
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(260.),
+                        height: Val::Px(56.),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    background_color: BUTTON_COLOR.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Continue anyway",
+                        style(32., TEXT_COLOR),
+                    ));
+                });
+        });
+}
+
+fn despawn_failure_screen(mut commands: Commands, query: Query<Entity, With<FailureScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Missing assets are replaced by defaults, so the game can still be played
+fn continue_after_failure(
+    controls: Res<ControlBinds>,
+    buttons: Query<&Interaction, Changed<Interaction>>,
+    mut state: ResMut<NextState<GameState>>,
+) {
+    let clicked = buttons
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed);
+    if clicked || controls.is_just_pressed(MenuAction::Select) {
+        state.set(GameState::Menu);
+    }
+}
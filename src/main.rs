@@ -5,7 +5,7 @@ use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 use bevy::winit::WinitWindows;
 use bevy::DefaultPlugins;
-use miner::GamePlugin;
+use miner::{replay::Replay, GamePlugin};
 use std::io::Cursor;
 use winit::window::Icon;
 
@@ -30,7 +30,8 @@ fn main() {
                 })
                 .set(ImagePlugin::default_nearest()),
         )
-        .add_plugins(GamePlugin)
+        .add_plugins(GamePlugin::default())
+        .insert_resource(Replay::from_args(std::env::args()))
         .add_systems(Startup, set_window_icon)
         .run();
 }
@@ -1,127 +1,266 @@
-use bevy::{asset::LoadState, prelude::*};
+use bevy::{prelude::*, utils::HashMap};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
-use crate::{resources::*, GameState, Tick};
+use crate::{
+    resources::*,
+    sorting::{SortLayer, MAP_LAYER, OBJECT_LAYER},
+    NewGame,
+};
 
-const MAP_LOAD_NAME: &str = "map";
 const MAP_TSET: &str = "Graphics/tiles.tset.ron";
 const MAP_SIZE: i32 = 100;
+// Side of a cell in pixels
+const TILE_SIZE: f32 = 32.;
+// World position of the first cell, the map is centered
+const MAP_ORIGIN: Vec2 = Vec2::splat(-(MAP_SIZE as f32 * TILE_SIZE) / 2.);
+// One in this many grass cells has ore, and one in this many has a tree
+const ORE_CHANCE: u32 = 8;
+const TREE_CHANCE: u32 = 12;
 
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.register_type::<MapAssets>()
-            .add_systems(OnEnter(crate::GameState::Playing), setup_map)
+        app.init_resource::<MapAssets>()
+            .init_resource::<MapTiles>()
+            .add_event::<SetTile>()
             .add_systems(
                 Update,
                 (
-                    check_load.run_if(in_state(crate::GameState::Loading)),
-                    update_tiles.run_if(in_state(GameState::Playing)),
+                    setup_map.in_set(crate::GameSetup),
+                    set_tiles.after(crate::GameSetup),
                 ),
-            )
-            .add_systems(OnEnter(crate::GameState::Loading), load_assets);
+            );
     }
 }
 
-#[derive(Component)]
-pub struct Tile;
+/// Loads the map texture set, tiles are drawn with it
+pub struct MapRenderPlugin;
+
+impl Plugin for MapRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_tset_collection::<MapAssets>()
+            .register_type::<MapAssets>();
+    }
+}
+
+/// Render layers of a map cell, later layers are drawn on top, buildings over all of them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MapLayer {
+    Ground,
+    Ore,
+    Building,
+    Decoration,
+}
+impl MapLayer {
+    pub const ALL: [MapLayer; 4] = [
+        MapLayer::Ground,
+        MapLayer::Ore,
+        MapLayer::Building,
+        MapLayer::Decoration,
+    ];
+    /// Depth above the map, all layers stay below the next sort layer
+    pub fn z(self) -> f32 {
+        self as u8 as f32
+    }
+    /// Buildings stand on the map like the player, so they are y-sorted with it
+    /// as entities of their own instead of being children of the map
+    pub fn is_object(self) -> bool {
+        self == MapLayer::Building
+    }
+}
+
+/// Sprite of one layer of a map cell
+#[derive(Component, Debug)]
+pub struct Tile {
+    pub cell: IVec2,
+    pub layer: MapLayer,
+}
+
+/// Tile entities of the current map by cell and layer
+#[derive(Resource, Default)]
+pub struct MapTiles {
+    map: Option<Entity>,
+    tiles: HashMap<(IVec2, MapLayer), Entity>,
+}
+impl MapTiles {
+    pub fn get(&self, cell: IVec2, layer: MapLayer) -> Option<Entity> {
+        self.tiles.get(&(cell, layer)).copied()
+    }
+    /// Layers of the cell which have a tile, from the bottom up
+    pub fn layers(&self, cell: IVec2) -> impl Iterator<Item = (MapLayer, Entity)> + '_ {
+        MapLayer::ALL
+            .into_iter()
+            .filter_map(move |layer| Some((layer, self.get(cell, layer)?)))
+    }
+}
+
+/// Places or replaces the tile of a layer in a cell, `None` clears the layer
+#[derive(Event)]
+pub struct SetTile {
+    pub cell: IVec2,
+    pub layer: MapLayer,
+    pub tile: Option<(String, TSetTile)>,
+}
+
+// Tiles of flat layers still have to be added to the map as children
+fn spawn_tile(
+    commands: &mut Commands,
+    tset: Handle<TextureSetAsset>,
+    cell: IVec2,
+    layer: MapLayer,
+    name: &str,
+    data: TSetTile,
+) -> Entity {
+    let position = cell.as_vec2() * TILE_SIZE;
+    let mut tile = commands.spawn((
+        SpriteSheetBundle {
+            transform: Transform::from_xyz(position.x, position.y, layer.z()),
+            sprite: TextureAtlasSprite {
+                custom_size: Some(Vec2::splat(TILE_SIZE)),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Tile { cell, layer },
+        TSetManager::new(tset, name, data),
+    ));
+    if layer.is_object() {
+        tile.insert((
+            Transform::from_translation((MAP_ORIGIN + position).extend(0.)),
+            SortLayer::sorted(OBJECT_LAYER),
+            crate::InGame,
+        ));
+    }
+    tile.id()
+}
 
 // logic
-fn setup_map(mut commands: Commands, assets: Res<MapAssets>) {
-    commands
+fn setup_map(
+    mut commands: Commands,
+    assets: Res<MapAssets>,
+    mut new_game: EventReader<NewGame>,
+    mut tiles: ResMut<MapTiles>,
+) {
+    let Some(seed) = new_game.read().last().map(|ev| ev.seed) else {
+        return;
+    };
+    let mut rng = StdRng::seed_from_u64(seed);
+    let map = commands
         .spawn((
             TransformBundle {
-                local: Transform::from_xyz(
-                    -(MAP_SIZE * 32) as f32 / 2.,
-                    -(MAP_SIZE * 32) as f32 / 2.,
-                    100.,
-                ),
+                local: Transform::from_translation(MAP_ORIGIN.extend(MAP_LAYER)),
                 ..Default::default()
             },
+            SortLayer::flat(MAP_LAYER),
             VisibilityBundle::default(),
             Name::new("Map"),
+            crate::InGame,
         ))
-        .with_children(|parent| {
-            for x in 0..MAP_SIZE {
-                for y in 0..MAP_SIZE {
-                    let rnd = rand::random::<u32>() % 5;
-                    let mut manager = TSetManager::new(
-                        assets.tileset.clone(),
-                        "grass",
-                        TSetTile::Variant("var1".to_string()),
-                    );
-                    if rnd == 1 {
-                        manager.set_tile("converyor", TSetTile::Animated(1));
-                    } else if rnd == 2 {
-                        manager.set_tile("water", TSetTile::Animated(1));
-                    } else if rnd == 3 {
-                        manager.set_tile("grass_to_water/left", TSetTile::Animated(1))
-                    } else if rnd == 4 {
-                        manager.set_tile("grass", TSetTile::Variant("var2".to_string()));
-                    }
-                    parent.spawn((
-                        SpriteSheetBundle {
-                            transform: Transform::from_xyz(32. * x as f32, 32. * y as f32, 0.),
-                            sprite: TextureAtlasSprite {
-                                custom_size: Some(Vec2::new(32., 32.)),
-                                ..Default::default()
-                            },
-                            ..Default::default()
-                        },
-                        Tile,
-                        manager,
-                    ));
+        .id();
+    let mut spawned = HashMap::new();
+    let mut children = Vec::new();
+    for x in 0..MAP_SIZE {
+        for y in 0..MAP_SIZE {
+            let cell = IVec2::new(x, y);
+            let mut spawn = |layer: MapLayer, name: &str, data| {
+                let tile = spawn_tile(
+                    &mut commands,
+                    assets.tileset.clone(),
+                    cell,
+                    layer,
+                    name,
+                    data,
+                );
+                if !layer.is_object() {
+                    children.push(tile);
                 }
+                spawned.insert((cell, layer), tile);
+            };
+            let rnd = rng.gen::<u32>() % 5;
+            let (ground, data) = match rnd {
+                2 => ("water", TSetTile::Animated(1)),
+                3 => ("grass_to_water/left", TSetTile::Animated(1)),
+                4 => ("grass", TSetTile::Variant("var2".to_string())),
+                _ => ("grass", TSetTile::Variant("var1".to_string())),
+            };
+            spawn(MapLayer::Ground, ground, data);
+            let on_grass = ground == "grass";
+            if on_grass && rng.gen_ratio(1, ORE_CHANCE) {
+                spawn(MapLayer::Ore, "ore", TSetTile::Single);
             }
-        });
-}
-fn update_tiles(mut tiles: Query<&mut TSetManager, With<Tile>>, tick: Res<Tick>) {
-    println!("{}", tick.0);
-    tiles.par_iter_mut().for_each(|mut tile| {
-        tile.update_frame(tick.0);
-    });
-}
-
-// Map assets
-#[derive(Resource, Reflect)]
-struct MapAssets {
-    tileset: Handle<crate::resources::TextureSetAsset>,
+            // Conveyors stand on grass and ore, trees grow where there is no building
+            if rnd == 1 {
+                spawn(MapLayer::Building, "converyor", TSetTile::Animated(1));
+            } else if on_grass && rng.gen_ratio(1, TREE_CHANCE) {
+                spawn(MapLayer::Decoration, "tree", TSetTile::Single);
+            }
+        }
+    }
+    commands.entity(map).push_children(&children);
+    *tiles = MapTiles {
+        map: Some(map),
+        tiles: spawned,
+    };
 }
 
-fn load_assets(
+fn set_tiles(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut load: ResMut<crate::LoadProcess>,
-) {
-    commands.insert_resource(MapAssets {
-        tileset: asset_server.load(MAP_TSET),
-    });
-    load.add(MAP_LOAD_NAME);
-}
-fn check_load(
-    mut assets: ResMut<MapAssets>,
-    server: Res<AssetServer>,
-    atlases: ResMut<'_, Assets<TextureAtlas>>,
-    images: ResMut<'_, Assets<Image>>,
-    mut tsets: ResMut<Assets<crate::resources::TextureSetAsset>>,
-    mut load: ResMut<crate::LoadProcess>,
+    mut events: EventReader<SetTile>,
+    mut tiles: ResMut<MapTiles>,
+    mut managers: Query<&mut TSetManager, With<Tile>>,
+    assets: Res<MapAssets>,
 ) {
-    if let Some(s) = server.get_load_state(assets.tileset.clone()) {
-        match s {
-            LoadState::NotLoaded => {
-                assets.tileset = server.load(MAP_TSET);
+    for ev in events.read() {
+        let Some(map) = tiles.map else {
+            warn!("No map to set the tile at {} on", ev.cell);
+            continue;
+        };
+        let key = (ev.cell, ev.layer);
+        match (tiles.tiles.get(&key).copied(), &ev.tile) {
+            // Tiles spawned in this frame have no manager yet, they are respawned
+            (Some(entity), Some((name, data))) if managers.contains(entity) => {
+                managers
+                    .get_mut(entity)
+                    .unwrap()
+                    .set_tile(name, data.clone());
             }
-            LoadState::Loading => {}
-            LoadState::Failed => {
-                warn!("Failed to load player texture set, using default");
-                assets.tileset = tsets.add(TextureSetAsset::default(server, atlases));
-                load.set(MAP_LOAD_NAME);
+            (old, Some((name, data))) => {
+                if let Some(old) = old {
+                    commands.entity(old).despawn_recursive();
+                }
+                let entity = spawn_tile(
+                    &mut commands,
+                    assets.tileset.clone(),
+                    ev.cell,
+                    ev.layer,
+                    name,
+                    data.clone(),
+                );
+                if !ev.layer.is_object() {
+                    commands.entity(map).add_child(entity);
+                }
+                tiles.tiles.insert(key, entity);
             }
-            LoadState::Loaded => {
-                let tset = tsets.get_mut(assets.tileset.clone()).unwrap();
-                tset.check_or_build(images, atlases);
-                load.set(MAP_LOAD_NAME);
+            (Some(entity), None) => {
+                commands.entity(entity).despawn_recursive();
+                tiles.tiles.remove(&key);
             }
+            (None, None) => {}
         }
     }
 }
+
+// Map assets
+#[derive(Resource, Reflect, Default)]
+struct MapAssets {
+    tileset: Handle<TextureSetAsset>,
+}
+impl TSetCollection for MapAssets {
+    const STEP: &'static str = "map";
+    // The map texture set is the largest one
+    const WEIGHT: f32 = 2.;
+    fn tsets(&mut self) -> Vec<(&mut Handle<TextureSetAsset>, &'static str)> {
+        vec![(&mut self.tileset, MAP_TSET)]
+    }
+}
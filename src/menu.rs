@@ -0,0 +1,345 @@
+use bevy::prelude::*;
+
+use crate::{
+    settings::{controls::*, SettingsReturn},
+    GameState, InGame, NewGame, FONT,
+};
+
+pub const MENU_BACKGROUND: Color = Color::rgb(0.1, 0.1, 0.1);
+const PAUSE_BACKGROUND: Color = Color::rgba(0., 0., 0., 0.6);
+pub const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+pub const FOCUSED_COLOR: Color = Color::rgb(0.35, 0.35, 0.35);
+pub const TEXT_COLOR: Color = Color::WHITE;
+const DISABLED_TEXT_COLOR: Color = Color::rgb(0.4, 0.4, 0.4);
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_actions::<MenuAction>()
+            .init_resource::<MenuFocus>()
+            .add_event::<MenuPressed>()
+            .add_systems(OnEnter(GameState::Menu), setup_main_menu)
+            .add_systems(OnExit(GameState::Menu), despawn_menu)
+            .add_systems(OnEnter(GameState::Paused), setup_pause_menu)
+            .add_systems(OnExit(GameState::Paused), despawn_menu)
+            .add_systems(
+                Update,
+                (
+                    toggle_pause
+                        .run_if(in_state(GameState::Playing).or_else(in_state(GameState::Paused))),
+                    (navigate_menu, mouse_menu, update_focus, press_button)
+                        .chain()
+                        .run_if(in_state(GameState::Menu).or_else(in_state(GameState::Paused))),
+                ),
+            );
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum MenuAction {
+    Pause,
+    Up,
+    Down,
+    Select,
+}
+impl Action for MenuAction {
+    const ALL: &'static [Self] = &[
+        MenuAction::Pause,
+        MenuAction::Up,
+        MenuAction::Down,
+        MenuAction::Select,
+    ];
+    fn name(&self) -> &'static str {
+        match self {
+            MenuAction::Pause => "pause",
+            MenuAction::Up => "menu_up",
+            MenuAction::Down => "menu_down",
+            MenuAction::Select => "menu_select",
+        }
+    }
+    fn default_binds(&self) -> Vec<Chord> {
+        let (key, button) = match self {
+            MenuAction::Pause => (KeyCode::Escape, GamepadButtonType::Start),
+            MenuAction::Up => (KeyCode::Up, GamepadButtonType::DPadUp),
+            MenuAction::Down => (KeyCode::Down, GamepadButtonType::DPadDown),
+            MenuAction::Select => (KeyCode::Return, GamepadButtonType::South),
+        };
+        vec![Bind::Key(key).into(), Bind::GamepadButton(button).into()]
+    }
+    fn context(&self) -> InputContext {
+        match self {
+            MenuAction::Pause => InputContext::Global,
+            _ => InputContext::Menu,
+        }
+    }
+    fn touch_bind(&self) -> Option<TouchBind> {
+        match self {
+            MenuAction::Pause => Some(TouchBind::Button(TouchButton::Pause)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Component)]
+struct Menu;
+
+#[derive(Component, Clone, Copy)]
+enum MenuButton {
+    NewGame,
+    Continue,
+    Settings,
+    Resume,
+    MainMenu,
+    #[cfg(not(target_arch = "wasm32"))]
+    Quit,
+}
+impl MenuButton {
+    fn label(&self) -> &'static str {
+        match self {
+            MenuButton::NewGame => "New Game",
+            MenuButton::Continue => "Continue",
+            MenuButton::Settings => "Settings",
+            MenuButton::Resume => "Resume",
+            MenuButton::MainMenu => "Main Menu",
+            #[cfg(not(target_arch = "wasm32"))]
+            MenuButton::Quit => "Quit",
+        }
+    }
+}
+
+/// Position of the button in its menu, used for keyboard and gamepad navigation
+#[derive(Component)]
+struct MenuItem(usize);
+
+/// Button is shown but can't be focused or pressed
+#[derive(Component)]
+struct Disabled;
+
+/// Index of the focused button among enabled buttons
+#[derive(Resource, Default)]
+struct MenuFocus(usize);
+
+#[derive(Event)]
+struct MenuPressed(MenuButton);
+
+// logic
+fn setup_main_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut focus: ResMut<MenuFocus>,
+    session: Query<(), With<InGame>>,
+) {
+    let has_session = !session.is_empty();
+    let buttons = [
+        (MenuButton::NewGame, true),
+        (MenuButton::Continue, has_session),
+        (MenuButton::Settings, true),
+        #[cfg(not(target_arch = "wasm32"))]
+        (MenuButton::Quit, true),
+    ];
+    spawn_menu(&mut commands, &asset_server, MENU_BACKGROUND, &buttons);
+    // Focus "Continue" if there is a game to return to
+    focus.0 = has_session as usize;
+}
+
+fn setup_pause_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut focus: ResMut<MenuFocus>,
+) {
+    let buttons = [
+        (MenuButton::Resume, true),
+        (MenuButton::Settings, true),
+        (MenuButton::MainMenu, true),
+        #[cfg(not(target_arch = "wasm32"))]
+        (MenuButton::Quit, true),
+    ];
+    spawn_menu(&mut commands, &asset_server, PAUSE_BACKGROUND, &buttons);
+    focus.0 = 0;
+}
+
+fn spawn_menu(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    background: Color,
+    buttons: &[(MenuButton, bool)],
+) {
+    let font = asset_server.load(FONT);
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.),
+                    ..Default::default()
+                },
+                background_color: background.into(),
+                z_index: ZIndex::Global(10),
+                ..Default::default()
+            },
+            Name::new("Menu"),
+            Menu,
+        ))
+        .with_children(|parent| {
+            for (i, (button, enabled)) in buttons.iter().enumerate() {
+                let mut entity = parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(260.),
+                            height: Val::Px(56.),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        background_color: BUTTON_COLOR.into(),
+                        ..Default::default()
+                    },
+                    *button,
+                    MenuItem(i),
+                ));
+                entity.with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        button.label(),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 32.,
+                            color: if *enabled {
+                                TEXT_COLOR
+                            } else {
+                                DISABLED_TEXT_COLOR
+                            },
+                        },
+                    ));
+                });
+                if !enabled {
+                    entity.insert(Disabled);
+                }
+            }
+        });
+}
+
+fn despawn_menu(mut commands: Commands, query: Query<Entity, With<Menu>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn toggle_pause(
+    mut controls: ResMut<ControlBinds>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !controls.is_just_pressed(MenuAction::Pause) {
+        return;
+    }
+    controls.consume(MenuAction::Pause);
+    match state.get() {
+        GameState::Playing => next_state.set(GameState::Paused),
+        GameState::Paused => next_state.set(GameState::Playing),
+        _ => {}
+    }
+}
+
+// Items sorted by their position in the menu
+fn enabled_items<'a>(
+    items: impl Iterator<Item = (&'a MenuItem, &'a MenuButton)>,
+) -> Vec<(usize, MenuButton)> {
+    let mut items: Vec<_> = items.map(|(item, button)| (item.0, *button)).collect();
+    items.sort_by_key(|(i, _)| *i);
+    items
+}
+
+fn navigate_menu(
+    controls: Res<ControlBinds>,
+    items: Query<(&MenuItem, &MenuButton), Without<Disabled>>,
+    mut focus: ResMut<MenuFocus>,
+    mut pressed: EventWriter<MenuPressed>,
+) {
+    let items = enabled_items(items.iter());
+    if items.is_empty() {
+        return;
+    }
+    if controls.is_just_pressed(MenuAction::Up) {
+        focus.0 = (focus.0 + items.len() - 1) % items.len();
+    }
+    if controls.is_just_pressed(MenuAction::Down) {
+        focus.0 = (focus.0 + 1) % items.len();
+    }
+    focus.0 = focus.0.min(items.len() - 1);
+    if controls.is_just_pressed(MenuAction::Select) {
+        pressed.send(MenuPressed(items[focus.0].1));
+    }
+}
+
+fn mouse_menu(
+    interactions: Query<(&Interaction, &MenuItem, &MenuButton), Changed<Interaction>>,
+    items: Query<(&MenuItem, &MenuButton), Without<Disabled>>,
+    mut focus: ResMut<MenuFocus>,
+    mut pressed: EventWriter<MenuPressed>,
+) {
+    let items = enabled_items(items.iter());
+    for (interaction, item, button) in interactions.iter() {
+        let Some(index) = items.iter().position(|(i, _)| *i == item.0) else {
+            continue;
+        };
+        match interaction {
+            Interaction::Hovered => focus.0 = index,
+            Interaction::Pressed => {
+                focus.0 = index;
+                pressed.send(MenuPressed(*button));
+            }
+            Interaction::None => {}
+        }
+    }
+}
+
+fn update_focus(
+    focus: Res<MenuFocus>,
+    mut items: Query<(&MenuItem, &mut BackgroundColor, Has<Disabled>)>,
+) {
+    let mut enabled: Vec<_> = items
+        .iter()
+        .filter(|(_, _, disabled)| !disabled)
+        .map(|(item, _, _)| item.0)
+        .collect();
+    enabled.sort();
+    let focused = enabled.get(focus.0).copied();
+    for (item, mut color, _) in items.iter_mut() {
+        *color = if Some(item.0) == focused {
+            FOCUSED_COLOR.into()
+        } else {
+            BUTTON_COLOR.into()
+        };
+    }
+}
+
+fn press_button(
+    mut commands: Commands,
+    mut events: EventReader<MenuPressed>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut new_game: EventWriter<NewGame>,
+    #[cfg(not(target_arch = "wasm32"))] mut exit: EventWriter<bevy::app::AppExit>,
+) {
+    for MenuPressed(button) in events.read() {
+        match button {
+            MenuButton::NewGame => {
+                new_game.send(NewGame::random());
+                next_state.set(GameState::Playing);
+            }
+            MenuButton::Continue | MenuButton::Resume => next_state.set(GameState::Playing),
+            MenuButton::Settings => {
+                commands.insert_resource(SettingsReturn(state.get().clone()));
+                next_state.set(GameState::Settings);
+            }
+            MenuButton::MainMenu => next_state.set(GameState::Menu),
+            #[cfg(not(target_arch = "wasm32"))]
+            MenuButton::Quit => exit.send(bevy::app::AppExit),
+        }
+    }
+}
@@ -1,34 +1,67 @@
+use crate::audio::PlaySfx;
+use crate::health::{Dead, DeathEvent, Health, Invulnerable};
 use crate::settings::controls::*;
-use crate::{resources::*, settings::ScaleSize};
-use bevy::{asset::LoadState, input::mouse::MouseWheel, prelude::*};
+use crate::{
+    resources::*,
+    settings::ScaleSize,
+    simulation::{interpolate, Interpolated, Simulation, TickRate},
+    sorting::{SortLayer, OBJECT_LAYER},
+};
+use bevy::{input::mouse::MouseWheel, prelude::*};
 
-const PLAYER_LOAD_NAME: &str = "player";
 const PLAYER_TSET: &str = "Graphics/robot.tset.ron";
+const PLAYER_HEALTH: u32 = 6;
+const PLAYER_SPAWN: Vec2 = Vec2::ZERO;
+const RESPAWN_TIME: f32 = 3.;
+// Distance between two footstep sounds, in pixels
+const STEP_DISTANCE: f32 = 48.;
+// Camera zoom limits in percents
+const MIN_ZOOM: f32 = 55.;
+const MAX_ZOOM: f32 = 200.;
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.register_type::<PlayerAssets>()
+        app.add_actions::<MoveAction>()
+            .init_resource::<PlayerAssets>()
             .register_type::<Player>()
-            .add_systems(OnEnter(crate::GameState::Playing), setup_player)
+            .add_systems(Update, setup_player.in_set(crate::GameSetup))
             .add_systems(
-                Update,
+                FixedUpdate,
                 (
-                    check_load.run_if(in_state(crate::GameState::Loading)),
-                    (move_player, scale_cam).run_if(in_state(crate::GameState::Playing)),
-                ),
+                    move_player,
+                    (player_death, respawn_player).after(crate::health::HealthSystems),
+                )
+                    .in_set(Simulation),
+            );
+    }
+}
+
+/// Player sprites, camera and footsteps
+pub struct PlayerRenderPlugin;
+
+impl Plugin for PlayerRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_tset_collection::<PlayerAssets>()
+            .register_type::<PlayerAssets>()
+            .add_systems(
+                Update,
+                (scale_cam, footsteps).run_if(in_state(crate::GameState::Playing)),
             )
-            .add_systems(OnEnter(crate::GameState::Loading), load_assets);
+            .add_systems(PostUpdate, follow_player.after(interpolate));
     }
 }
 
 // logic
 fn setup_player(mut commands: Commands, assets: Res<PlayerAssets>) {
     commands.spawn((
-        Player { speed: 5. },
+        Player { speed: 300. },
+        Health::new(PLAYER_HEALTH),
+        Interpolated::new(PLAYER_SPAWN),
+        SortLayer::sorted(OBJECT_LAYER),
         SpriteSheetBundle {
-            transform: Transform::from_xyz(0., 0., 999.),
+            transform: Transform::from_translation(PLAYER_SPAWN.extend(0.)),
             sprite: TextureAtlasSprite {
                 custom_size: Some(Vec2::new(64., 64.)),
                 ..Default::default()
@@ -36,39 +69,172 @@ fn setup_player(mut commands: Commands, assets: Res<PlayerAssets>) {
             ..Default::default()
         },
         Name::new("Player"),
-        TSetManager::new(assets.tileset.clone(), "left", TSetTile::Single),
+        crate::InGame,
+        Facing(Vec2::NEG_X),
+        TSetManager::new(
+            assets.tileset.clone(),
+            "robot",
+            TSetTile::Direction(Vec2::NEG_X),
+        ),
     ));
 }
 
+#[derive(Clone, Copy)]
+enum MoveAction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+impl Action for MoveAction {
+    const ALL: &'static [Self] = &[
+        MoveAction::Up,
+        MoveAction::Down,
+        MoveAction::Left,
+        MoveAction::Right,
+    ];
+    fn name(&self) -> &'static str {
+        match self {
+            MoveAction::Up => "move_up",
+            MoveAction::Down => "move_down",
+            MoveAction::Left => "move_left",
+            MoveAction::Right => "move_right",
+        }
+    }
+    fn default_binds(&self) -> Vec<Chord> {
+        use AxisDirection::*;
+        use GamepadAxisType::*;
+        let (key, stick) = match self {
+            MoveAction::Up => (KeyCode::W, Bind::stick(LeftStickY, Positive)),
+            MoveAction::Down => (KeyCode::S, Bind::stick(LeftStickY, Negative)),
+            MoveAction::Left => (KeyCode::A, Bind::stick(LeftStickX, Negative)),
+            MoveAction::Right => (KeyCode::D, Bind::stick(LeftStickX, Positive)),
+        };
+        vec![Bind::Key(key).into(), stick.into()]
+    }
+    fn touch_bind(&self) -> Option<TouchBind> {
+        Some(match self {
+            MoveAction::Up => TouchBind::Up,
+            MoveAction::Down => TouchBind::Down,
+            MoveAction::Left => TouchBind::Left,
+            MoveAction::Right => TouchBind::Right,
+        })
+    }
+}
+
 #[derive(Component, Reflect)]
-struct Player {
+pub struct Player {
+    /// Pixels per second
     speed: f32,
 }
 
+/// Ticks left until the player is back at the spawn
+#[derive(Component)]
+struct Respawn(u32);
+
 fn move_player(
-    mut player: Query<(&Player, &mut Transform, &mut TSetManager)>,
-    mut cam: Query<&mut Transform, (With<Camera>, Without<Player>)>,
+    mut player: Query<(&Player, &mut Interpolated, &mut Facing), Without<Dead>>,
     controls: Res<ControlBinds>,
+    time: Res<Time>,
 ) {
-    let move_x = controls.is_pressed("move_right") as i32 - controls.is_pressed("move_left") as i32;
-    let move_y = controls.is_pressed("move_up") as i32 - controls.is_pressed("move_down") as i32;
-
-    let (pl, mut trans, mut manager) = player.single_mut();
-    if move_x > 0 {
-        manager.set_tile("right", TSetTile::Single);
-    } else if move_x < 0 {
-        manager.set_tile("left", TSetTile::Single);
-    } else if move_y > 0 {
-        manager.set_tile("up", TSetTile::Single);
-    } else if move_y < 0 {
-        manager.set_tile("down", TSetTile::Single);
+    // Sticks give partial values, so the player walks slower with a small deflection
+    let move_x = controls.value(MoveAction::Right) - controls.value(MoveAction::Left);
+    let move_y = controls.value(MoveAction::Up) - controls.value(MoveAction::Down);
+
+    let Ok((pl, mut position, mut facing)) = player.get_single_mut() else {
+        return;
+    };
+    // Standing still keeps the last facing
+    let direction = Vec2::new(move_x, move_y);
+    if direction != Vec2::ZERO {
+        facing.0 = direction;
     }
 
-    trans.translation.x += move_x as f32 * pl.speed;
-    trans.translation.y += move_y as f32 * pl.speed;
+    // Time is fixed here, so the same input always moves the player the same distance
+    position.current += Vec2::new(move_x, move_y) * pl.speed * time.delta_seconds();
+}
 
-    let mut cam = cam.single_mut();
-    cam.translation = Vec3::new(trans.translation.x, trans.translation.y, cam.translation.z);
+fn follow_player(
+    player: Query<&Transform, With<Player>>,
+    mut cam: Query<&mut Transform, (With<Camera>, Without<Player>)>,
+) {
+    let (Ok(player), Ok(mut cam)) = (player.get_single(), cam.get_single_mut()) else {
+        return;
+    };
+    cam.translation = Vec3::new(
+        player.translation.x,
+        player.translation.y,
+        cam.translation.z,
+    );
+}
+
+fn footsteps(
+    player: Query<&Interpolated, (With<Player>, Without<Dead>)>,
+    mut sfx: EventWriter<PlaySfx>,
+    mut last: Local<Option<Vec2>>,
+    mut walked: Local<f32>,
+) {
+    let Ok(position) = player.get_single() else {
+        *last = None;
+        return;
+    };
+    let position = position.current;
+    // A jump like a respawn is not a step
+    let step = last.map_or(0., |last| last.distance(position));
+    if step < STEP_DISTANCE {
+        *walked += step;
+    }
+    *last = Some(position);
+    if *walked >= STEP_DISTANCE {
+        *walked = 0.;
+        sfx.send(PlaySfx::at("footstep", position));
+    }
+}
+
+fn player_death(
+    mut commands: Commands,
+    mut deaths: EventReader<DeathEvent>,
+    mut player: Query<&mut Visibility, With<Player>>,
+    rate: Res<TickRate>,
+) {
+    for DeathEvent(entity) in deaths.read() {
+        if let Ok(mut visibility) = player.get_mut(*entity) {
+            info!("Player is dead, respawn in {RESPAWN_TIME}s");
+            *visibility = Visibility::Hidden;
+            commands
+                .entity(*entity)
+                .insert(Respawn(rate.ticks(RESPAWN_TIME)));
+        }
+    }
+}
+
+fn respawn_player(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &mut Respawn,
+            &mut Health,
+            &mut Interpolated,
+            &mut Visibility,
+        ),
+        With<Player>,
+    >,
+    rate: Res<TickRate>,
+) {
+    for (entity, mut respawn, mut health, mut position, mut visibility) in player.iter_mut() {
+        respawn.0 = respawn.0.saturating_sub(1);
+        if respawn.0 > 0 {
+            continue;
+        }
+        position.teleport(PLAYER_SPAWN);
+        health.current = health.max;
+        *visibility = Visibility::Inherited;
+        commands
+            .entity(entity)
+            .remove::<(Respawn, Dead)>()
+            .insert(Invulnerable::new(&rate));
+    }
 }
 
 #[derive(Component)]
@@ -80,73 +246,34 @@ impl Default for PlayerCamera {
         Self { scale: 1. }
     }
 }
+impl PlayerCamera {
+    /// Changes the zoom in percents, positive `amount` zooms out
+    pub fn zoom(&mut self, amount: f32) {
+        self.scale = (self.scale + amount).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+}
 
 fn scale_cam(
     mut cam: Query<(&mut Transform, &mut PlayerCamera), (With<Camera>, Without<Player>)>,
     mut scroll_evr: EventReader<MouseWheel>,
     scale: Res<ScaleSize>,
 ) {
-    use bevy::input::mouse::MouseScrollUnit;
     let (mut trans, mut cam) = cam.single_mut();
+    // Lines and pixels zoom the same, `zoom` keeps the scale in its limits
     for ev in scroll_evr.read() {
-        match ev.unit {
-            MouseScrollUnit::Line => {
-                cam.scale -= scale.0 as f32 * ev.y;
-            }
-            MouseScrollUnit::Pixel => {
-                cam.scale -= scale.0 as f32 * ev.y;
-            }
-        }
-    }
-    if cam.scale < 55. {
-        cam.scale = 55.;
-    }
-    if cam.scale > 200. {
-        cam.scale = 200.;
+        cam.zoom(-scale.0 * ev.y);
     }
     trans.scale = Vec3::new(cam.scale / 100., cam.scale / 100., 1.);
 }
 
 // PL assets
-#[derive(Resource, Reflect)]
+#[derive(Resource, Reflect, Default)]
 struct PlayerAssets {
-    tileset: Handle<crate::resources::TextureSetAsset>,
+    tileset: Handle<TextureSetAsset>,
 }
-
-fn load_assets(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut load: ResMut<crate::LoadProcess>,
-) {
-    commands.insert_resource(PlayerAssets {
-        tileset: asset_server.load(PLAYER_TSET),
-    });
-    load.add(PLAYER_LOAD_NAME);
-}
-fn check_load(
-    mut assets: ResMut<PlayerAssets>,
-    server: Res<AssetServer>,
-    atlases: ResMut<'_, Assets<TextureAtlas>>,
-    images: ResMut<'_, Assets<Image>>,
-    mut tsets: ResMut<Assets<crate::resources::TextureSetAsset>>,
-    mut load: ResMut<crate::LoadProcess>,
-) {
-    if let Some(s) = server.get_load_state(assets.tileset.clone()) {
-        match s {
-            LoadState::NotLoaded => {
-                assets.tileset = server.load(PLAYER_TSET);
-            }
-            LoadState::Loading => {}
-            LoadState::Failed => {
-                warn!("Failed to load player texture set, using default");
-                assets.tileset = tsets.add(TextureSetAsset::default(server, atlases));
-                load.set(PLAYER_LOAD_NAME);
-            }
-            LoadState::Loaded => {
-                let tset = tsets.get_mut(assets.tileset.clone()).unwrap();
-                tset.check_or_build(images, atlases);
-                load.set(PLAYER_LOAD_NAME);
-            }
-        }
+impl TSetCollection for PlayerAssets {
+    const STEP: &'static str = "player";
+    fn tsets(&mut self) -> Vec<(&mut Handle<TextureSetAsset>, &'static str)> {
+        vec![(&mut self.tileset, PLAYER_TSET)]
     }
 }
@@ -1,18 +1,37 @@
 use bevy::input::common_conditions::input_toggle_active;
 use bevy::prelude::*;
-pub struct Plugins;
+pub struct Plugins {
+    pub render: bool,
+}
 
 impl Plugin for Plugins {
     fn build(&self, app: &mut App) {
         app.add_plugins((
-            crate::resources::ResourcesPlugin,
             crate::player::PlayerPlugin,
+            crate::health::HealthPlugin,
             crate::map::MapPlugin,
             crate::settings::SettingsPlugin,
             crate::LoadPlugin,
+            crate::replay::ReplayPlugin,
+            crate::simulation::SimulationPlugin,
+            crate::sorting::SortingPlugin,
+        ));
+        if !self.render {
+            return;
+        }
+        app.add_plugins((
+            crate::resources::ResourcesPlugin,
+            crate::audio::GameAudioPlugin,
+            crate::player::PlayerRenderPlugin,
+            crate::hud::HudPlugin,
+            crate::map::MapRenderPlugin,
+            crate::settings::SettingsRenderPlugin,
+            crate::LoadRenderPlugin,
+            crate::menu::MenuPlugin,
+            crate::touch::TouchPlugin,
             #[cfg(debug_assertions)]
             bevy_inspector_egui::quick::WorldInspectorPlugin::default()
-                .run_if(input_toggle_active(true, KeyCode::Escape)),
+                .run_if(input_toggle_active(true, KeyCode::F1)),
         ));
     }
 }
@@ -0,0 +1,275 @@
+use std::path::PathBuf;
+
+use bevy::{app::AppExit, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    settings::{controls::*, ScaleSize},
+    simulation::{Simulation, TickRate},
+    GameState, NewGame, Tick,
+};
+
+// Ticks between saves while recording, so a crash loses at most this much.
+// Each save rewrites the whole file on the main thread, it stays cheap because
+// only ticks with active world actions are stored
+const SAVE_TICKS: u64 = 1800;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Replay>()
+            .add_systems(Startup, open_replay)
+            // Input is stored per tick, so it doesn't depend on the frame rate
+            .add_systems(
+                FixedUpdate,
+                (
+                    (record_frame, feed_frame).before(Simulation),
+                    save_recording.run_if(is_save_tick),
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), save_recording)
+            .add_systems(
+                Update,
+                (
+                    start_replay.run_if(in_state(GameState::Menu)),
+                    start_recording.in_set(crate::GameSetup),
+                ),
+            )
+            .add_systems(Last, save_recording.run_if(on_event::<AppExit>()));
+    }
+}
+
+/// Everything needed to play a session again
+#[derive(Serialize, Deserialize, Default)]
+pub struct Recording {
+    seed: u64,
+    scale_size: f32,
+    /// Frames are stored by tick, they only line up at the same rate
+    #[serde(default = "default_tick_rate")]
+    tick_rate: f64,
+    /// Ticks where any world action is active, in order
+    frames: Vec<Frame>,
+}
+
+// Recordings saved before the rate was stored ran at the default one
+fn default_tick_rate() -> f64 {
+    TickRate::default().0
+}
+
+#[derive(Serialize, Deserialize)]
+struct Frame {
+    tick: u64,
+    /// Action name, its state and value
+    actions: Vec<(String, ActionState, f32)>,
+}
+
+/// Set by the `--record <file>` and `--replay <file>` arguments, inserted by the binary
+#[derive(Resource, Default)]
+pub enum Replay {
+    #[default]
+    Off,
+    Record {
+        path: PathBuf,
+        recording: Option<Recording>,
+    },
+    Play {
+        path: PathBuf,
+        /// Loaded at startup
+        recording: Recording,
+        started: bool,
+        /// Index of the next frame to feed
+        next: usize,
+    },
+}
+impl Replay {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        while let Some(arg) = args.next() {
+            if arg != "--record" && arg != "--replay" {
+                continue;
+            }
+            // A missing path is reported at startup when logging works
+            let path = PathBuf::from(args.next().unwrap_or_default());
+            return if arg == "--record" {
+                Replay::Record {
+                    path,
+                    recording: None,
+                }
+            } else {
+                Replay::Play {
+                    path,
+                    recording: Recording::default(),
+                    started: false,
+                    next: 0,
+                }
+            };
+        }
+        Replay::Off
+    }
+}
+
+// Only actions which change the world are recorded, menus stay usable while replaying
+fn is_recorded(binds: &ControlBinds, action: ActionId) -> bool {
+    matches!(
+        binds.context(action),
+        Some(InputContext::Gameplay | InputContext::Build)
+    )
+}
+
+// `is_multiple_of` needs a newer compiler than the game asks for
+#[allow(unknown_lints, clippy::manual_is_multiple_of)]
+fn is_save_tick(tick: Res<Tick>) -> bool {
+    tick.0 % SAVE_TICKS == 0
+}
+
+// logic
+fn open_replay(mut replay: ResMut<Replay>) {
+    match replay.as_mut() {
+        Replay::Off => {}
+        Replay::Record { path, .. } if path.as_os_str().is_empty() => {
+            warn!("--record needs a file path");
+            *replay = Replay::Off;
+        }
+        Replay::Record { path, .. } => info!("Recording input to {}", path.display()),
+        Replay::Play {
+            path, recording, ..
+        } => {
+            let loaded = std::fs::read_to_string(&*path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| ron::de::from_str(&data).map_err(|e| e.to_string()));
+            match loaded {
+                Ok(loaded) => {
+                    info!("Replaying {}", path.display());
+                    *recording = loaded;
+                }
+                Err(e) => {
+                    warn!("Failed to load replay {}: {e}", path.display());
+                    *replay = Replay::Off;
+                }
+            }
+        }
+    }
+}
+
+fn start_recording(
+    mut replay: ResMut<Replay>,
+    mut new_game: EventReader<NewGame>,
+    scale: Res<ScaleSize>,
+    rate: Res<TickRate>,
+) {
+    let Some(seed) = new_game.read().last().map(|ev| ev.seed) else {
+        return;
+    };
+    // Only the last game is kept
+    if let Replay::Record { recording, .. } = replay.as_mut() {
+        *recording = Some(Recording {
+            seed,
+            scale_size: scale.0,
+            tick_rate: rate.0,
+            frames: Vec::new(),
+        });
+    }
+}
+
+fn record_frame(mut replay: ResMut<Replay>, binds: Res<ControlBinds>, tick: Res<Tick>) {
+    let Replay::Record {
+        recording: Some(recording),
+        ..
+    } = replay.as_mut()
+    else {
+        return;
+    };
+    let actions: Vec<_> = binds
+        .iter()
+        .map(|(action, _)| action)
+        .filter(|action| is_recorded(&binds, *action))
+        .map(|action| (action, binds.state(action), binds.value(action)))
+        .filter(|(_, state, value)| *state != ActionState::default() || *value != 0.)
+        .map(|(action, state, value)| (action.name().to_string(), state, value))
+        .collect();
+    if !actions.is_empty() {
+        recording.frames.push(Frame {
+            tick: tick.0,
+            actions,
+        });
+    }
+}
+
+fn start_replay(
+    mut replay: ResMut<Replay>,
+    mut scale: ResMut<ScaleSize>,
+    mut rate: ResMut<TickRate>,
+    mut new_game: EventWriter<NewGame>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if let Replay::Play {
+        recording, started, ..
+    } = replay.as_mut()
+    {
+        if !*started {
+            *started = true;
+            scale.0 = recording.scale_size;
+            rate.0 = recording.tick_rate;
+            new_game.send(NewGame {
+                seed: recording.seed,
+            });
+            next_state.set(GameState::Playing);
+        }
+    }
+}
+
+fn feed_frame(mut replay: ResMut<Replay>, mut binds: ResMut<ControlBinds>, tick: Res<Tick>) {
+    let Replay::Play {
+        recording, next, ..
+    } = replay.as_mut()
+    else {
+        return;
+    };
+    if *next >= recording.frames.len() {
+        return;
+    }
+    // Actions which are not in the frame were released at this tick
+    let ids: Vec<_> = binds.iter().map(|(action, _)| action).collect();
+    for action in ids {
+        if is_recorded(&binds, action) {
+            binds.set_state(action, ActionState::default(), 0.);
+        }
+    }
+    let frame = &recording.frames[*next];
+    if frame.tick > tick.0 {
+        return;
+    }
+    for (name, state, value) in &frame.actions {
+        match binds.find(name) {
+            Some(action) => binds.set_state(action, *state, *value),
+            None => warn!("Replayed action {name} is not registered"),
+        }
+    }
+    *next += 1;
+    if *next == recording.frames.len() {
+        info!("Replay is finished, input is returned to the player");
+    }
+}
+
+// Runs on leaving the game, on exit and now and then while playing
+fn save_recording(replay: Res<Replay>) {
+    let Replay::Record {
+        path,
+        recording: Some(recording),
+    } = replay.as_ref()
+    else {
+        return;
+    };
+    let data = match ron::ser::to_string_pretty(recording, ron::ser::PrettyConfig::default()) {
+        Ok(data) => data,
+        Err(e) => {
+            warn!("Failed to serialize the recording: {e}");
+            return;
+        }
+    };
+    match std::fs::write(path, data) {
+        Ok(()) => info!("Recording is saved to {}", path.display()),
+        Err(e) => warn!("Failed to save the recording: {e}"),
+    }
+}
@@ -0,0 +1,123 @@
+use bevy::{asset::RecursiveDependencyLoadState, prelude::*};
+
+use super::TextureSetAsset;
+use crate::{GameState, LoadProcess, StepState, ASSET_TIMEOUT};
+
+/// Resource with texture sets of a plugin, registered with [`AddTSetCollection::add_tset_collection`]
+///
+/// Sets are loaded when loading starts, atlases are built once they are loaded and
+/// failed sets, sets whose atlas can't be built, or sets still loading when loading ends,
+/// are replaced with the default one.
+pub trait TSetCollection: Resource + Default {
+    /// Name of the load step
+    const STEP: &'static str;
+    /// Share of the loading bar
+    const WEIGHT: f32 = 1.;
+    /// Every texture set handle with the path it is loaded from
+    fn tsets(&mut self) -> Vec<(&mut Handle<TextureSetAsset>, &'static str)>;
+}
+
+pub trait AddTSetCollection {
+    fn add_tset_collection<C: TSetCollection>(&mut self) -> &mut Self;
+}
+impl AddTSetCollection for App {
+    fn add_tset_collection<C: TSetCollection>(&mut self) -> &mut Self {
+        self.init_resource::<C>()
+            .add_systems(OnEnter(GameState::Loading), load_collection::<C>)
+            .add_systems(OnExit(GameState::Loading), finish_collection::<C>)
+            .add_systems(
+                Update,
+                check_collection::<C>.run_if(in_state(GameState::Loading)),
+            )
+    }
+}
+
+// logic
+fn load_collection<C: TSetCollection>(
+    mut collection: ResMut<C>,
+    server: Res<AssetServer>,
+    mut load: ResMut<LoadProcess>,
+) {
+    for (handle, path) in collection.tsets() {
+        *handle = server.load(path);
+    }
+    load.add(C::STEP).weight(C::WEIGHT).timeout(ASSET_TIMEOUT);
+}
+
+fn check_collection<C: TSetCollection>(
+    mut collection: ResMut<C>,
+    server: Res<AssetServer>,
+    mut atlases: ResMut<Assets<TextureAtlas>>,
+    mut images: ResMut<Assets<Image>>,
+    mut tsets: ResMut<Assets<TextureSetAsset>>,
+    mut load: ResMut<LoadProcess>,
+) {
+    if !matches!(
+        load.state(C::STEP),
+        Some(StepState::Pending | StepState::Loading)
+    ) {
+        return;
+    }
+    let mut handles = collection.tsets();
+    let states: Vec<_> = handles
+        .iter()
+        .map(|(handle, _)| server.get_recursive_dependency_load_state(handle.id()))
+        .collect();
+    let finished = states.iter().all(|state| {
+        matches!(
+            state,
+            Some(RecursiveDependencyLoadState::Loaded | RecursiveDependencyLoadState::Failed)
+        )
+    });
+    if !finished {
+        load.start(C::STEP);
+        return;
+    }
+
+    let mut failed = Vec::new();
+    for ((handle, path), state) in handles.iter_mut().zip(states) {
+        if state == Some(RecursiveDependencyLoadState::Failed) {
+            warn!("Failed to load texture set {path}, using default");
+            **handle = tsets.add(TextureSetAsset::default(&server, &mut atlases));
+            failed.push(path.to_string());
+        } else if let Some(tset) = tsets.get_mut(&**handle) {
+            if let Err(e) = tset.check_or_build(&mut images, &mut atlases) {
+                warn!("Failed to build the atlas of {path}, using default: {e}");
+                **handle = tsets.add(TextureSetAsset::default(&server, &mut atlases));
+                failed.push(format!("{path} ({e})"));
+            }
+        }
+    }
+    if failed.is_empty() {
+        load.set(C::STEP);
+    } else {
+        load.fail(
+            C::STEP,
+            format!("{} failed to load, default is used", failed.join(", ")),
+        );
+    }
+}
+
+// A timed out step leaves loading early, sets which are not loaded by then are never built
+fn finish_collection<C: TSetCollection>(
+    mut collection: ResMut<C>,
+    server: Res<AssetServer>,
+    mut atlases: ResMut<Assets<TextureAtlas>>,
+    mut images: ResMut<Assets<Image>>,
+    mut tsets: ResMut<Assets<TextureSetAsset>>,
+) {
+    for (handle, path) in collection.tsets() {
+        let built = match tsets.get_mut(&*handle) {
+            Some(tset) => tset.check_or_build(&mut images, &mut atlases),
+            None => {
+                warn!("Texture set {path} is not loaded in time, using default");
+                *handle = tsets.add(TextureSetAsset::default(&server, &mut atlases));
+                continue;
+            }
+        };
+        if let Err(e) = built {
+            warn!("Failed to build the atlas of {path}, using default: {e}");
+            *handle = tsets.add(TextureSetAsset::default(&server, &mut atlases));
+        }
+    }
+}
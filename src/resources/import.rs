@@ -0,0 +1,434 @@
+use std::fmt;
+
+use bevy::utils::{thiserror, HashMap};
+use serde::{
+    de::{MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use thiserror::Error;
+
+use super::{Frame, FrameDuration, SheetRect, SourceLink, Tile, TileGroup};
+
+// Duration of a frame without one, in milliseconds
+const FRAME_DURATION: f32 = 100.;
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("Could not parse JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("frame {0} is rotated, rotated frames are not supported")]
+    Rotated(String),
+    #[error("frame {0} is trimmed, export the sheet without trimming")]
+    Trimmed(String),
+    #[error("tag {tag} uses frame {frame}, the sheet has {frames} frames")]
+    MissingFrame {
+        tag: String,
+        frame: usize,
+        frames: usize,
+    },
+    #[error("tag {tag} goes from frame {from} back to {to}")]
+    ReversedTag { tag: String, from: usize, to: usize },
+}
+
+/// Rects and tiles of a sheet exported by Aseprite or TexturePacker
+pub struct ImportedSheet {
+    /// Path of the image, relative to the JSON file
+    pub image: String,
+    pub rects: HashMap<String, SheetRect>,
+    /// Frames and slices as single tiles, frame tags as animations
+    pub tiles: TileGroup,
+}
+
+/// Reads JSON with a hash or an array of frames, tiles link to the source named `source`
+pub fn import_json(bytes: &[u8], source: &str) -> Result<ImportedSheet, ImportError> {
+    let sheet: JsonSheet = serde_json::from_slice(bytes)?;
+    let frames = sheet.frames.0;
+    let link = |name: &str| SourceLink::Rect {
+        source: source.to_string(),
+        name: name.to_string(),
+    };
+
+    let mut rects = HashMap::new();
+    let mut tiles = TileGroup::new();
+    for (name, frame) in frames.iter() {
+        if frame.rotated {
+            return Err(ImportError::Rotated(name.clone()));
+        }
+        // The empty border is cut off, the sprite would be shifted without it
+        if frame.trimmed {
+            return Err(ImportError::Trimmed(name.clone()));
+        }
+        rects.insert(name.clone(), frame.frame.into());
+        tiles.insert(tile_name(name), Tile::Single(link(name)));
+    }
+
+    // Slice bounds are inside the frame, the first key is used
+    for slice in sheet.meta.slices.iter() {
+        let Some(key) = slice.keys.first() else {
+            continue;
+        };
+        let Some((_, frame)) = frames.get(key.frame) else {
+            continue;
+        };
+        let mut rect: SheetRect = key.bounds.into();
+        rect.position += SheetRect::from(frame.frame).position;
+        let name = format!("slice/{}", slice.name);
+        rects.insert(name.clone(), rect);
+        tiles.insert(slice.name.clone(), Tile::Single(link(&name)));
+    }
+
+    // Checked before the frames of a tag are listed, so a huge range doesn't allocate
+    for tag in sheet.meta.frame_tags.iter() {
+        if tag.from > tag.to {
+            return Err(ImportError::ReversedTag {
+                tag: tag.name.clone(),
+                from: tag.from,
+                to: tag.to,
+            });
+        }
+        if tag.to >= frames.len() {
+            return Err(ImportError::MissingFrame {
+                tag: tag.name.clone(),
+                frame: tag.to,
+                frames: frames.len(),
+            });
+        }
+    }
+
+    // Durations stay in milliseconds, they are counted in ticks when the tile is shown
+    for tag in sheet.meta.frame_tags.iter() {
+        let order = tag.frames();
+        let mut tag_frames = Vec::with_capacity(order.len());
+        for index in order {
+            let (name, frame) = &frames[index];
+            let duration = frame.duration.unwrap_or(FRAME_DURATION);
+            tag_frames.push(Frame {
+                duration: Some(FrameDuration::Millis(duration)),
+                ..link(name).into()
+            });
+        }
+        tiles.insert(
+            tag.name.clone(),
+            Tile::Animated {
+                frames: tag_frames,
+                // Every frame has a duration
+                speed: 1.,
+            },
+        );
+    }
+
+    Ok(ImportedSheet {
+        image: sheet.meta.image,
+        rects,
+        tiles,
+    })
+}
+
+// Frame names are file names like `robot 0.png`, tiles go without the extension
+fn tile_name(frame: &str) -> String {
+    match frame.rsplit_once('.') {
+        Some((name, _)) if !name.is_empty() => name.to_string(),
+        _ => frame.to_string(),
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonSheet {
+    frames: Frames,
+    meta: JsonMeta,
+}
+
+#[derive(Deserialize)]
+struct JsonFrame {
+    /// Only set when frames are an array
+    #[serde(default)]
+    filename: Option<String>,
+    frame: JsonRect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    /// Milliseconds, only Aseprite has it
+    #[serde(default)]
+    duration: Option<f32>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct JsonRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+impl From<JsonRect> for SheetRect {
+    fn from(rect: JsonRect) -> Self {
+        Self {
+            position: bevy::math::Vec2::new(rect.x, rect.y),
+            size: bevy::math::Vec2::new(rect.w, rect.h),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonMeta {
+    image: String,
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<JsonTag>,
+    #[serde(default)]
+    slices: Vec<JsonSlice>,
+}
+
+#[derive(Deserialize)]
+struct JsonTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+}
+impl JsonTag {
+    /// Frame numbers in the order they are played
+    fn frames(&self) -> Vec<usize> {
+        let forward: Vec<usize> = (self.from..=self.to).collect();
+        let mut reverse = forward.clone();
+        reverse.reverse();
+        // The ends are not repeated when the direction turns
+        let back = |frames: &[usize]| frames[1..frames.len().saturating_sub(1)].to_vec();
+        match self.direction.as_str() {
+            "reverse" => reverse,
+            "pingpong" if forward.len() > 2 => [forward.clone(), back(&reverse)].concat(),
+            "pingpong_reverse" if forward.len() > 2 => [reverse.clone(), back(&forward)].concat(),
+            "pingpong_reverse" => reverse,
+            _ => forward,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonSlice {
+    name: String,
+    keys: Vec<JsonSliceKey>,
+}
+
+#[derive(Deserialize)]
+struct JsonSliceKey {
+    frame: usize,
+    bounds: JsonRect,
+}
+
+/// Frames with their names in the file order, from a hash or an array
+struct Frames(Vec<(String, JsonFrame)>);
+
+impl<'de> Deserialize<'de> for Frames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FramesVisitor;
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = Frames;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a hash or an array of frames")
+            }
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Frames, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(entry) = map.next_entry::<String, JsonFrame>()? {
+                    frames.push(entry);
+                }
+                Ok(Frames(frames))
+            }
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Frames, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(frame) = seq.next_element::<JsonFrame>()? {
+                    let name = frame
+                        .filename
+                        .clone()
+                        .unwrap_or_else(|| frames.len().to_string());
+                    frames.push((name, frame));
+                }
+                Ok(Frames(frames))
+            }
+        }
+        deserializer.deserialize_any(FramesVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec2;
+
+    use super::*;
+
+    const ASEPRITE: &str = r##"{
+        "frames": {
+            "robot 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "rotated": false, "trimmed": false, "duration": 100 },
+            "robot 1.aseprite": { "frame": { "x": 16, "y": 0, "w": 16, "h": 16 }, "rotated": false, "trimmed": false, "duration": 100 },
+            "robot 2.aseprite": { "frame": { "x": 32, "y": 0, "w": 16, "h": 16 }, "rotated": false, "trimmed": false, "duration": 200 },
+            "robot 3.aseprite": { "frame": { "x": 48, "y": 0, "w": 16, "h": 16 }, "rotated": false, "trimmed": false, "duration": 200 }
+        },
+        "meta": {
+            "app": "https://www.aseprite.org/",
+            "image": "robot.png",
+            "size": { "w": 64, "h": 16 },
+            "frameTags": [
+                { "name": "walk", "from": 0, "to": 1, "direction": "forward" },
+                { "name": "spin", "from": 0, "to": 3, "direction": "pingpong" },
+                { "name": "back", "from": 2, "to": 3, "direction": "reverse" }
+            ],
+            "slices": [
+                { "name": "eye", "color": "#0000ffff", "keys": [{ "frame": 1, "bounds": { "x": 4, "y": 2, "w": 3, "h": 3 } }] }
+            ]
+        }
+    }"##;
+
+    const TEXTURE_PACKER: &str = r##"{
+        "frames": [
+            { "filename": "drill.png", "frame": { "x": 0, "y": 0, "w": 32, "h": 24 }, "rotated": false },
+            { "filename": "belt.png", "frame": { "x": 32, "y": 0, "w": 16, "h": 8 }, "rotated": false }
+        ],
+        "meta": { "app": "https://www.codeandweb.com/texturepacker", "image": "machines.png" }
+    }"##;
+
+    fn frame_names(tile: &Tile) -> Vec<String> {
+        let Tile::Animated { frames, .. } = tile else {
+            panic!("not animated");
+        };
+        frames
+            .iter()
+            .map(|frame| match &frame.link {
+                SourceLink::Rect { name, .. } => name.clone(),
+                _ => panic!("not a rect link"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn aseprite_frames_and_tags() {
+        let sheet = import_json(ASEPRITE.as_bytes(), "robot").unwrap();
+        assert_eq!(sheet.image, "robot.png");
+        assert_eq!(
+            sheet.rects["robot 2.aseprite"],
+            SheetRect {
+                position: Vec2::new(32., 0.),
+                size: Vec2::splat(16.)
+            }
+        );
+        assert!(matches!(
+            &sheet.tiles["robot 1"],
+            Tile::Single(SourceLink::Rect { source, name }) if source == "robot" && name == "robot 1.aseprite"
+        ));
+        assert_eq!(
+            frame_names(&sheet.tiles["walk"]),
+            ["robot 0.aseprite", "robot 1.aseprite"]
+        );
+        assert_eq!(
+            frame_names(&sheet.tiles["back"]),
+            ["robot 3.aseprite", "robot 2.aseprite"]
+        );
+        let spin: Vec<_> = ["0", "1", "2", "3", "2", "1"]
+            .map(|frame| format!("robot {frame}.aseprite"))
+            .into();
+        assert_eq!(frame_names(&sheet.tiles["spin"]), spin);
+    }
+
+    #[test]
+    fn aseprite_durations_in_millis() {
+        let sheet = import_json(ASEPRITE.as_bytes(), "robot").unwrap();
+        let durations = |tag: &str| {
+            let Tile::Animated { frames, .. } = &sheet.tiles[tag] else {
+                panic!("not animated");
+            };
+            frames
+                .iter()
+                .map(|frame| match frame.duration {
+                    Some(FrameDuration::Millis(ms)) => ms,
+                    _ => panic!("no duration in milliseconds"),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(durations("walk"), [100., 100.]);
+        assert_eq!(durations("back"), [200., 200.]);
+        assert_eq!(durations("spin"), [100., 100., 200., 200., 200., 100.]);
+    }
+
+    #[test]
+    fn aseprite_slices_in_sheet_space() {
+        let sheet = import_json(ASEPRITE.as_bytes(), "robot").unwrap();
+        assert_eq!(
+            sheet.rects["slice/eye"],
+            SheetRect {
+                position: Vec2::new(20., 2.),
+                size: Vec2::splat(3.)
+            }
+        );
+        assert!(matches!(
+            &sheet.tiles["eye"],
+            Tile::Single(SourceLink::Rect { name, .. }) if name == "slice/eye"
+        ));
+    }
+
+    #[test]
+    fn texture_packer_array() {
+        let sheet = import_json(TEXTURE_PACKER.as_bytes(), "machines").unwrap();
+        assert_eq!(sheet.image, "machines.png");
+        assert_eq!(sheet.rects.len(), 2);
+        assert_eq!(sheet.rects["belt.png"].size, Vec2::new(16., 8.));
+        assert!(matches!(
+            &sheet.tiles["drill"],
+            Tile::Single(SourceLink::Rect { name, .. }) if name == "drill.png"
+        ));
+    }
+
+    #[test]
+    fn rejects_broken_sheets() {
+        let rotated = TEXTURE_PACKER.replacen("\"rotated\": false", "\"rotated\": true", 1);
+        assert!(matches!(
+            import_json(rotated.as_bytes(), "machines"),
+            Err(ImportError::Rotated(name)) if name == "drill.png"
+        ));
+        let trimmed = TEXTURE_PACKER.replacen(
+            "\"rotated\": false }",
+            "\"rotated\": false, \"trimmed\": true }",
+            2,
+        );
+        assert!(matches!(
+            import_json(trimmed.as_bytes(), "machines"),
+            Err(ImportError::Trimmed(name)) if name == "drill.png"
+        ));
+        let missing = ASEPRITE.replace("\"to\": 1,", "\"to\": 9,");
+        assert!(matches!(
+            import_json(missing.as_bytes(), "robot"),
+            Err(ImportError::MissingFrame {
+                frame: 9,
+                frames: 4,
+                ..
+            })
+        ));
+        let huge = ASEPRITE.replace("\"to\": 1,", &format!("\"to\": {},", usize::MAX));
+        assert!(matches!(
+            import_json(huge.as_bytes(), "robot"),
+            Err(ImportError::MissingFrame { .. })
+        ));
+        let reversed = ASEPRITE.replace("\"from\": 2, \"to\": 3", "\"from\": 3, \"to\": 2");
+        assert!(matches!(
+            import_json(reversed.as_bytes(), "robot"),
+            Err(ImportError::ReversedTag { from: 3, to: 2, .. })
+        ));
+        assert!(matches!(
+            import_json(b"{}", "robot"),
+            Err(ImportError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn tag_directions() {
+        let tag = |from, to, direction: &str| JsonTag {
+            name: String::new(),
+            from,
+            to,
+            direction: direction.to_string(),
+        };
+        assert_eq!(tag(0, 3, "").frames(), [0, 1, 2, 3]);
+        assert_eq!(tag(0, 3, "pingpong").frames(), [0, 1, 2, 3, 2, 1]);
+        assert_eq!(tag(0, 3, "pingpong_reverse").frames(), [3, 2, 1, 0, 1, 2]);
+        assert_eq!(tag(2, 3, "pingpong").frames(), [2, 3]);
+        assert_eq!(tag(5, 5, "reverse").frames(), [5]);
+    }
+}
@@ -1,4 +1,10 @@
+mod collection;
+pub mod import;
+pub mod slicing;
 pub mod tset;
+pub use collection::*;
+pub use import::*;
+pub use slicing::*;
 pub use tset::*;
 
 use bevy::asset::embedded_asset;
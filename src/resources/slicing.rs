@@ -0,0 +1,313 @@
+use bevy::{prelude::*, utils::thiserror};
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Where cell numbers of a source start
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Indexing {
+    /// The top left cell is `Vec2(1, 1)`
+    #[default]
+    OneBased,
+    /// The top left cell is `Vec2(0, 0)`
+    ZeroBased,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SliceError {
+    #[error("cell {index} is outside of the {columns}x{rows} grid")]
+    OutOfRange {
+        index: Vec2,
+        columns: u32,
+        rows: u32,
+    },
+    #[error("cell {0} is not a whole number")]
+    NotWhole(Vec2),
+    #[error("rect {0} is not in the sheet")]
+    UnknownRect(String),
+    #[error("{part} doesn't fit into the {image} image")]
+    OutsideImage { part: String, image: Vec2 },
+}
+
+/// How a source image is cut into equal cells, row by row
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid {
+    pub tile_size: Vec2,
+    pub rows: u32,
+    pub columns: u32,
+    /// Gap between two neighbouring cells
+    pub padding: Vec2,
+    /// Gap between the image border and the first row and column
+    pub margin: Vec2,
+    pub indexing: Indexing,
+}
+impl Grid {
+    pub fn new(tile_size: Vec2, rows: u32, columns: u32) -> Self {
+        Self {
+            tile_size,
+            rows,
+            columns,
+            padding: Vec2::ZERO,
+            margin: Vec2::ZERO,
+            indexing: Indexing::default(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        (self.rows * self.columns) as usize
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Position of the cell in [`Grid::rects`]
+    pub fn cell(&self, index: Vec2) -> Result<usize, SliceError> {
+        let first = match self.indexing {
+            Indexing::OneBased => 1.,
+            Indexing::ZeroBased => 0.,
+        };
+        let cell = index - first;
+        if cell.fract() != Vec2::ZERO {
+            return Err(SliceError::NotWhole(index));
+        }
+        if cell.x < 0. || cell.y < 0. || cell.x >= self.columns as f32 || cell.y >= self.rows as f32
+        {
+            return Err(SliceError::OutOfRange {
+                index,
+                columns: self.columns,
+                rows: self.rows,
+            });
+        }
+        Ok(cell.y as usize * self.columns as usize + cell.x as usize)
+    }
+    /// Rect of every cell, `origin` is the top left corner of the image
+    pub fn rects(&self, origin: Vec2) -> Vec<Rect> {
+        let mut rects = Vec::with_capacity(self.len());
+        for y in 0..self.rows {
+            for x in 0..self.columns {
+                let cell = Vec2::new(x as f32, y as f32);
+                let min = origin + self.margin + (self.tile_size + self.padding) * cell;
+                rects.push(Rect::from_corners(min, min + self.tile_size));
+            }
+        }
+        rects
+    }
+    /// Space the grid takes from the top left corner of the image
+    pub fn size(&self) -> Vec2 {
+        let cells = Vec2::new(self.columns as f32, self.rows as f32);
+        let gaps = (cells - 1.).max(Vec2::ZERO);
+        self.margin + self.tile_size * cells + self.padding * gaps
+    }
+    /// Fails if the grid reaches out of an image of `size`
+    pub fn check(&self, size: Vec2) -> Result<(), SliceError> {
+        if self.size().cmpgt(size).any() {
+            return Err(SliceError::OutsideImage {
+                part: "grid".to_string(),
+                image: size,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Part of a packed sheet, in pixels from the top left corner of the image
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SheetRect {
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+/// Packed sheet with named parts of any size
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sheet {
+    /// Sorted by name, so cells keep their numbers between loads
+    rects: Vec<(String, SheetRect)>,
+}
+impl Sheet {
+    pub fn new(rects: impl IntoIterator<Item = (String, SheetRect)>) -> Self {
+        let mut rects: Vec<_> = rects.into_iter().collect();
+        rects.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Self { rects }
+    }
+    pub fn len(&self) -> usize {
+        self.rects.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+    /// Position of the named rect in [`Sheet::rects`]
+    pub fn cell(&self, name: &str) -> Result<usize, SliceError> {
+        self.rects
+            .binary_search_by(|(rect, _)| rect.as_str().cmp(name))
+            .map_err(|_| SliceError::UnknownRect(name.to_string()))
+    }
+    /// Every rect in the atlas, `origin` is the top left corner of the image
+    pub fn rects(&self, origin: Vec2) -> Vec<Rect> {
+        self.rects
+            .iter()
+            .map(|(_, rect)| {
+                let min = origin + rect.position;
+                Rect::from_corners(min, min + rect.size)
+            })
+            .collect()
+    }
+    /// Fails on the first rect which reaches out of an image of `size`
+    pub fn check(&self, size: Vec2) -> Result<(), SliceError> {
+        for (name, rect) in self.rects.iter() {
+            let outside = rect.position.cmplt(Vec2::ZERO).any()
+                || (rect.position + rect.size).cmpgt(size).any();
+            if outside {
+                return Err(SliceError::OutsideImage {
+                    part: format!("rect {name}"),
+                    image: size,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> Grid {
+        Grid::new(Vec2::splat(16.), 2, 3)
+    }
+
+    #[test]
+    fn one_based_cells() {
+        let grid = grid();
+        assert_eq!(grid.cell(Vec2::new(1., 1.)), Ok(0));
+        assert_eq!(grid.cell(Vec2::new(3., 1.)), Ok(2));
+        assert_eq!(grid.cell(Vec2::new(1., 2.)), Ok(3));
+        assert_eq!(grid.cell(Vec2::new(3., 2.)), Ok(5));
+    }
+
+    #[test]
+    fn zero_based_cells() {
+        let grid = Grid {
+            indexing: Indexing::ZeroBased,
+            ..grid()
+        };
+        assert_eq!(grid.cell(Vec2::new(0., 0.)), Ok(0));
+        assert_eq!(grid.cell(Vec2::new(2., 1.)), Ok(5));
+    }
+
+    #[test]
+    fn out_of_range_cells() {
+        let grid = grid();
+        for index in [
+            Vec2::new(0., 1.),
+            Vec2::new(1., 0.),
+            Vec2::new(4., 1.),
+            Vec2::new(1., 3.),
+            Vec2::new(-1., -1.),
+        ] {
+            assert_eq!(
+                grid.cell(index),
+                Err(SliceError::OutOfRange {
+                    index,
+                    columns: 3,
+                    rows: 2
+                })
+            );
+        }
+        let zero_based = Grid {
+            indexing: Indexing::ZeroBased,
+            ..grid
+        };
+        assert!(zero_based.cell(Vec2::new(3., 0.)).is_err());
+        assert!(zero_based.cell(Vec2::new(0., 2.)).is_err());
+    }
+
+    #[test]
+    fn fractional_cells() {
+        let index = Vec2::new(1.5, 1.);
+        assert_eq!(grid().cell(index), Err(SliceError::NotWhole(index)));
+    }
+
+    #[test]
+    fn rects_row_by_row() {
+        let rects = grid().rects(Vec2::new(100., 50.));
+        assert_eq!(rects.len(), 6);
+        assert_eq!(rects[0].min, Vec2::new(100., 50.));
+        assert_eq!(rects[1].min, Vec2::new(116., 50.));
+        assert_eq!(rects[3].min, Vec2::new(100., 66.));
+        assert!(rects.iter().all(|rect| rect.size() == Vec2::splat(16.)));
+    }
+
+    #[test]
+    fn rects_with_padding_and_margin() {
+        let grid = Grid {
+            padding: Vec2::new(2., 4.),
+            margin: Vec2::new(1., 3.),
+            ..grid()
+        };
+        let rects = grid.rects(Vec2::ZERO);
+        assert_eq!(rects[0].min, Vec2::new(1., 3.));
+        assert_eq!(rects[2].min, Vec2::new(1. + 2. * 18., 3.));
+        assert_eq!(rects[4].min, Vec2::new(1. + 18., 3. + 20.));
+        assert_eq!(rects[4].max, rects[4].min + Vec2::splat(16.));
+    }
+
+    #[test]
+    fn grid_size() {
+        let grid = Grid {
+            padding: Vec2::new(2., 4.),
+            margin: Vec2::new(1., 3.),
+            ..grid()
+        };
+        assert_eq!(
+            grid.size(),
+            Vec2::new(1. + 3. * 16. + 2. * 2., 3. + 2. * 16. + 4.)
+        );
+        assert_eq!(grid.check(grid.size()), Ok(()));
+        assert!(grid.check(grid.size() - Vec2::X).is_err());
+        assert!(grid.check(grid.size() - Vec2::Y).is_err());
+    }
+
+    fn sheet() -> Sheet {
+        let rect = |x, y, w, h| SheetRect {
+            position: Vec2::new(x, y),
+            size: Vec2::new(w, h),
+        };
+        Sheet::new([
+            ("walk".to_string(), rect(8., 0., 12., 6.)),
+            ("idle".to_string(), rect(0., 0., 8., 10.)),
+        ])
+    }
+
+    #[test]
+    fn sheet_cells_by_name() {
+        let sheet = sheet();
+        assert_eq!(sheet.cell("idle"), Ok(0));
+        assert_eq!(sheet.cell("walk"), Ok(1));
+        assert_eq!(
+            sheet.cell("jump"),
+            Err(SliceError::UnknownRect("jump".to_string()))
+        );
+        let rects = sheet.rects(Vec2::new(100., 50.));
+        assert_eq!(rects[0], Rect::new(100., 50., 108., 60.));
+        assert_eq!(rects[1], Rect::new(108., 50., 120., 56.));
+    }
+
+    #[test]
+    fn sheet_outside_image() {
+        let sheet = sheet();
+        assert_eq!(sheet.check(Vec2::new(20., 10.)), Ok(()));
+        assert_eq!(
+            sheet.check(Vec2::new(19., 10.)),
+            Err(SliceError::OutsideImage {
+                part: "rect walk".to_string(),
+                image: Vec2::new(19., 10.)
+            })
+        );
+        assert!(sheet.check(Vec2::new(20., 9.)).is_err());
+    }
+
+    #[test]
+    fn empty_grid() {
+        let grid = Grid::new(Vec2::splat(16.), 0, 4);
+        assert!(grid.is_empty());
+        assert!(grid.rects(Vec2::ZERO).is_empty());
+        assert!(grid.cell(Vec2::ONE).is_err());
+    }
+}
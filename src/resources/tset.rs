@@ -8,12 +8,15 @@ use bevy::{
     asset::{io::Reader, AssetLoader, LoadContext},
     prelude::*,
     reflect::TypePath,
+    sprite::{Anchor, TextureAtlasBuilderError},
     utils::BoxedFuture,
 };
 use futures_lite::AsyncReadExt;
 use serde::Deserialize;
 use thiserror::Error;
 
+use super::{import_json, Grid, ImportError, Indexing, Sheet, SheetRect, SliceError};
+
 const DEFAULT_TEXTURE: &str = "embedded://miner/resources/default.png";
 
 pub struct TSetPlugin;
@@ -23,7 +26,8 @@ impl Plugin for TSetPlugin {
         app.init_asset::<TextureSetAsset>()
             .init_asset_loader::<TextureSetLoader>()
             .register_type::<TSetManager>()
-            .add_systems(PostUpdate, update);
+            .register_type::<Facing>()
+            .add_systems(PostUpdate, (animate_tiles, (update, update_ui)).chain());
     }
 }
 #[derive(Deserialize, Debug, Clone)]
@@ -33,23 +37,176 @@ enum SourceConfig {
         rows: u32,
         columns: u32,
         tile_size: Vec2,
+        /// Gap between two neighbouring cells
+        #[serde(default)]
+        padding: Vec2,
+        /// Gap before the first row and column, like in `TextureAtlas::from_grid`
+        #[serde(default, alias = "margin")]
+        offset: Vec2,
+        /// Numbers of the cells in tile links
+        #[serde(default)]
+        indexing: Indexing,
     },
     Texture {
         source: String,
     },
+    /// Packed sheet with named parts, linked by name
+    Rects {
+        source: String,
+        rects: HashMap<String, SheetRect>,
+    },
+    /// Aseprite or TexturePacker JSON, its tiles are in a group named after the source
+    Json {
+        source: String,
+    },
 }
 #[derive(Deserialize, Debug, Clone)]
 pub enum SourceLink {
     Atlas { source: String, index: Vec2 },
     Texture { source: String },
+    Rect { source: String, name: String },
+}
+impl SourceLink {
+    pub fn source(&self) -> &str {
+        match self {
+            SourceLink::Atlas { source, .. }
+            | SourceLink::Texture { source }
+            | SourceLink::Rect { source, .. } => source,
+        }
+    }
 }
 
-type TileGroup = HashMap<String, Tile>;
+pub type TileGroup = HashMap<String, Tile>;
+
+/// Frame of an animated tile, written as a plain link or as `Frame(link: ..)`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(from = "FrameConfig")]
+pub struct Frame {
+    pub link: SourceLink,
+    /// How long the frame is shown, `1 / speed` ticks of the tile if not set
+    pub duration: Option<FrameDuration>,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Moves the sprite by pixels, y is up
+    pub offset: Vec2,
+}
+impl From<SourceLink> for Frame {
+    fn from(link: SourceLink) -> Self {
+        Self {
+            link,
+            duration: None,
+            flip_x: false,
+            flip_y: false,
+            offset: Vec2::ZERO,
+        }
+    }
+}
+
+// Same variants as `SourceLink`, so old sets with links as frames still load
+#[derive(Deserialize)]
+enum FrameConfig {
+    Atlas {
+        source: String,
+        index: Vec2,
+    },
+    Texture {
+        source: String,
+    },
+    Rect {
+        source: String,
+        name: String,
+    },
+    Frame {
+        link: SourceLink,
+        #[serde(default, deserialize_with = "some_duration")]
+        duration: Option<FrameDuration>,
+        #[serde(default)]
+        flip_x: bool,
+        #[serde(default)]
+        flip_y: bool,
+        #[serde(default)]
+        offset: Vec2,
+    },
+}
+impl From<FrameConfig> for Frame {
+    fn from(cfg: FrameConfig) -> Self {
+        match cfg {
+            FrameConfig::Atlas { source, index } => SourceLink::Atlas { source, index }.into(),
+            FrameConfig::Texture { source } => SourceLink::Texture { source }.into(),
+            FrameConfig::Rect { source, name } => SourceLink::Rect { source, name }.into(),
+            FrameConfig::Frame {
+                link,
+                duration,
+                flip_x,
+                flip_y,
+                offset,
+            } => Self {
+                link,
+                duration,
+                flip_x,
+                flip_y,
+                offset,
+            },
+        }
+    }
+}
+
+// Lets sets write `duration: 3` ticks instead of `duration: Some(3)`
+fn some_duration<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<FrameDuration>, D::Error> {
+    f32::deserialize(deserializer).map(|ticks| Some(FrameDuration::Ticks(ticks)))
+}
+
+/// How long a frame is shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameDuration {
+    Ticks(f32),
+    /// Imported from Aseprite, the ticks follow the tick rate
+    Millis(f32),
+}
+impl FrameDuration {
+    /// Length in ticks when one tick lasts `tick_length` seconds
+    pub fn ticks(&self, tick_length: f64) -> f64 {
+        match *self {
+            FrameDuration::Ticks(ticks) => ticks as f64,
+            FrameDuration::Millis(ms) => ms as f64 / 1000. / tick_length,
+        }
+    }
+}
+
+/// Frame shown on `tick`, frames without a duration last `1 / speed` ticks.
+/// A tick lasts `tick_length` seconds
+pub fn frame_at(frames: &[Frame], speed: f32, tick: u64, tick_length: f64) -> Option<&Frame> {
+    let duration = |frame: &Frame| {
+        frame
+            .duration
+            .map_or(1. / speed as f64, |duration| duration.ticks(tick_length))
+    };
+    let total: f64 = frames.iter().map(duration).sum();
+    if !(total.is_finite() && total > 0.) {
+        return frames.first();
+    }
+    let mut time = tick as f64 % total;
+    for frame in frames {
+        if time < duration(frame) {
+            return Some(frame);
+        }
+        time -= duration(frame);
+    }
+    frames.last()
+}
+
+fn default_speed() -> f32 {
+    1.
+}
 
 #[derive(Deserialize, Debug, Clone)]
 pub enum Tile {
     Animated {
-        frames: Vec<SourceLink>,
+        frames: Vec<Frame>,
+        /// Frames per tick for frames without a duration
+        #[serde(default = "default_speed")]
         speed: f32,
     },
     Single(SourceLink),
@@ -57,6 +214,39 @@ pub enum Tile {
         variants: HashMap<String, SourceLink>,
     },
     Group(TileGroup),
+    /// Sub-tiles by facing, keys are `up`, `down`, `left`, `right` and diagonals like `up_left`
+    Directional {
+        directions: HashMap<String, Tile>,
+    },
+}
+
+const DIRECTIONS: [(&str, Vec2); 8] = [
+    ("right", Vec2::X),
+    ("left", Vec2::NEG_X),
+    ("up", Vec2::Y),
+    ("down", Vec2::NEG_Y),
+    ("up_right", Vec2::new(1., 1.)),
+    ("up_left", Vec2::new(-1., 1.)),
+    ("down_right", Vec2::new(1., -1.)),
+    ("down_left", Vec2::new(-1., -1.)),
+];
+
+/// Key of the direction closest to `facing` among `keys`, horizontal ones win ties
+pub fn closest_direction(keys: impl Fn(&str) -> bool, facing: Vec2) -> Option<&'static str> {
+    let facing = facing.try_normalize()?;
+    let mut best: Option<(&'static str, f32)> = None;
+    for (name, direction) in DIRECTIONS {
+        if !keys(name) {
+            continue;
+        }
+        let dot = direction.normalize().dot(facing);
+        // Rounding keeps exact ties from being decided by float noise
+        let dot = (dot * 1e4).round();
+        if !matches!(best, Some((_, best)) if dot <= best) {
+            best = Some((name, dot));
+        }
+    }
+    best.map(|(name, _)| name)
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -69,23 +259,23 @@ pub enum PreSource {
     TextureAtlas {
         img: Image,
         hdl: Handle<Image>,
-        rows: u32,
-        columns: u32,
-        tile_size: Vec2,
+        grid: Grid,
     },
     Texture {
         img: Image,
         hdl: Handle<Image>,
     },
+    Rects {
+        img: Image,
+        hdl: Handle<Image>,
+        sheet: Sheet,
+    },
 }
 #[derive(Debug)]
 pub enum SourceInfo {
-    Atlas {
-        offset: usize,
-        rows: u32,
-        columns: u32,
-    },
+    Atlas { offset: usize, grid: Grid },
     Texture(usize),
+    Rects { offset: usize, sheet: Sheet },
 }
 
 #[derive(Asset, TypePath, Debug)]
@@ -118,104 +308,147 @@ impl TextureSetAsset {
         }
         Some(now.clone())
     }
-    pub fn index_and_atlas(&self, link: SourceLink) -> (usize, Handle<TextureAtlas>) {
-        let (atlas, offsets) = self.sources.as_ref().unwrap();
-        let mut atlas = atlas.clone();
-        let mut index = 0;
-        if let SourceLink::Texture { source } = link {
-            match offsets.get(&source) {
-                Some(SourceInfo::Texture(offset)) => {
-                    index = *offset;
-                }
-                _ => {
-                    if source == "_@default" {
-                        index = 0;
-                        atlas = self.default.clone();
-                    } else {
-                        panic!("Source from link is not exist");
+    pub fn resolve(&self, manager: &TSetManager) -> (usize, Handle<TextureAtlas>) {
+        self.index_and_atlas(self.frame(manager).link)
+    }
+    /// Link of the tile shown by the manager now, with its flips and offset
+    pub fn frame(&self, manager: &TSetManager) -> Frame {
+        self.tile_frame(self.get_tile(&manager.tile_name), &manager.data, manager)
+    }
+    fn tile_frame(&self, tile: Tile, data: &TSetTile, manager: &TSetManager) -> Frame {
+        let default = Frame::from(SourceLink::Texture {
+            source: "_@default".to_string(),
+        });
+        match tile {
+            Tile::Animated { frames, speed } => {
+                if let TSetTile::Animated(tick) = data {
+                    match frame_at(&frames, speed, *tick, manager.tick_length) {
+                        Some(frame) => frame.clone(),
+                        None => {
+                            warn!("Animated tile doesn't have frames! Using default");
+                            default
+                        }
                     }
+                } else {
+                    warn!("Uncorrect tile!");
+                    default
                 }
             }
-        } else if let SourceLink::Atlas {
-            source,
-            index: index2,
-        } = link
-        {
-            match offsets.get(&source) {
-                Some(SourceInfo::Atlas {
-                    offset,
-                    rows: _,
-                    columns,
-                }) => {
-                    index = offset
-                        + (index2.y - 1.) as usize * *columns as usize
-                        + (index2.x - 1.) as usize;
-                }
-                _ => {
-                    if source == "_@default" {
-                        index = 0;
-                        atlas = self.default.clone();
-                    } else {
-                        panic!("Source from link is not exist");
+            Tile::Single(link) => link.into(),
+            Tile::Group(_map) => {
+                warn!("Tile is group. Don't use tile. Using default");
+                default
+            }
+            Tile::Variant { variants } => {
+                if let TSetTile::Variant(name) = data {
+                    match variants.get(name) {
+                        Some(var) => var.clone().into(),
+                        None => {
+                            warn!("Variant is not exist. Using default");
+                            default
+                        }
                     }
+                } else {
+                    warn!("Uncorrect tile");
+                    default
                 }
             }
+            Tile::Directional { mut directions } => {
+                let TSetTile::Direction(facing) = data else {
+                    warn!("Uncorrect tile");
+                    return default;
+                };
+                let Some(name) = closest_direction(|name| directions.contains_key(name), *facing)
+                else {
+                    warn!("No direction for {facing}. Using default");
+                    return default;
+                };
+                // Animated sub-tiles play with the frame of the manager
+                let sub = directions.remove(name).unwrap();
+                let data = match sub {
+                    Tile::Animated { .. } => TSetTile::Animated(manager.tick),
+                    _ => TSetTile::Single,
+                };
+                self.tile_frame(sub, &data, manager)
+            }
+        }
+    }
+    pub fn index_and_atlas(&self, link: SourceLink) -> (usize, Handle<TextureAtlas>) {
+        let source = link.source();
+        if source == "_@default" {
+            return (0, self.default.clone());
+        }
+        let Some((atlas, offsets)) = self.sources.as_ref() else {
+            warn!("Texture set atlas is not built, using default");
+            return (0, self.default.clone());
+        };
+        let Some(info) = offsets.get(source) else {
+            warn!("Source {source} is not in the texture set, using default");
+            return (0, self.default.clone());
+        };
+        let index = match (info, &link) {
+            (SourceInfo::Texture(offset), SourceLink::Texture { .. }) => Ok(*offset),
+            (SourceInfo::Atlas { offset, grid }, SourceLink::Atlas { index, .. }) => {
+                grid.cell(*index).map(|cell| offset + cell)
+            }
+            (SourceInfo::Rects { offset, sheet }, SourceLink::Rect { name, .. }) => {
+                sheet.cell(name).map(|cell| offset + cell)
+            }
+            _ => {
+                warn!("Link to {source} doesn't match the source kind, using default");
+                return (0, self.default.clone());
+            }
+        };
+        match index {
+            Ok(index) => (index, atlas.clone()),
+            Err(err) => {
+                warn!("Source {source}: {err}, using default");
+                (0, self.default.clone())
+            }
         }
-        (index, atlas.clone())
     }
 
+    /// Builds the atlas of the loaded sources, once. Fails when the sources don't fit into one atlas
     pub fn check_or_build(
         &mut self,
-        mut assets: ResMut<Assets<Image>>,
-        mut atlases: ResMut<Assets<TextureAtlas>>,
-    ) {
+        assets: &mut Assets<Image>,
+        atlases: &mut Assets<TextureAtlas>,
+    ) -> Result<(), TextureAtlasBuilderError> {
         if self.pre_src.is_none() {
-            return;
+            return Ok(());
         }
         let pre_src = self.pre_src.as_ref().unwrap();
         let mut builder = TextureAtlasBuilder::default();
 
         for (_src_name, i) in pre_src.iter() {
             match i {
-                PreSource::TextureAtlas {
-                    img,
-                    hdl,
-                    rows: _,
-                    columns: _,
-                    tile_size: _,
-                } => {
+                PreSource::TextureAtlas { img, hdl, grid: _ } => {
                     builder.add_texture(hdl.id(), img);
                 }
                 PreSource::Texture { img, hdl } => {
                     builder.add_texture(hdl.id(), img);
                 }
+                PreSource::Rects { img, hdl, sheet: _ } => {
+                    builder.add_texture(hdl.id(), img);
+                }
             }
         }
 
-        let mut texture_atlas = builder
-            .finish(&mut assets)
-            .expect("Cannot build texture atlas");
+        let mut texture_atlas = builder.finish(assets)?;
         let len = texture_atlas.textures.len();
         let mut index = len;
         let mut src_info = HashMap::new();
         for (src_name, i) in pre_src.iter() {
             match i {
-                PreSource::TextureAtlas {
-                    img: _,
-                    hdl,
-                    rows,
-                    columns,
-                    tile_size,
-                } => {
+                PreSource::TextureAtlas { img: _, hdl, grid } => {
                     let index_offset = texture_atlas.get_texture_index(hdl.id()).unwrap();
                     let rect = texture_atlas.textures.get(index_offset).unwrap();
-                    let rects = rects(*tile_size, *rows, *columns, rect.min);
+                    let rects = grid.rects(rect.min);
                     src_info.insert(
                         src_name.clone(),
                         SourceInfo::Atlas {
                             offset: index,
-                            rows: *rows,
-                            columns: *columns,
+                            grid: *grid,
                         },
                     );
                     index += rects.len();
@@ -229,16 +462,30 @@ impl TextureSetAsset {
                         SourceInfo::Texture(texture_atlas.get_texture_index(hdl.id()).unwrap()),
                     );
                 }
+                PreSource::Rects { img: _, hdl, sheet } => {
+                    let index_offset = texture_atlas.get_texture_index(hdl.id()).unwrap();
+                    let rect = texture_atlas.textures.get(index_offset).unwrap();
+                    let rects = sheet.rects(rect.min);
+                    src_info.insert(
+                        src_name.clone(),
+                        SourceInfo::Rects {
+                            offset: index,
+                            sheet: sheet.clone(),
+                        },
+                    );
+                    index += rects.len();
+                    for i in rects.iter() {
+                        texture_atlas.add_texture(*i);
+                    }
+                }
             }
         }
         let hdl = atlases.add(texture_atlas);
         self.sources = Some((hdl, src_info));
         self.pre_src = None;
+        Ok(())
     }
-    pub fn default(
-        asset_server: Res<AssetServer>,
-        mut atlases: ResMut<Assets<TextureAtlas>>,
-    ) -> Self {
+    pub fn default(asset_server: &AssetServer, atlases: &mut Assets<TextureAtlas>) -> Self {
         let mut sources = HashMap::new();
         sources.insert(String::from("@default"), SourceInfo::Texture(0));
         let atlas = TextureAtlas::from_grid(
@@ -260,7 +507,7 @@ impl TextureSetAsset {
 }
 
 #[derive(Default)]
-struct TextureSetLoader;
+pub struct TextureSetLoader;
 
 #[non_exhaustive]
 #[derive(Debug, Error)]
@@ -275,6 +522,10 @@ pub enum TextureSetLoaderError {
     ReadAssetBytesError(#[from] ReadAssetBytesError),
     #[error("Failed to make image from bytes: {0}")]
     TextureError(#[from] TextureError),
+    #[error("Source {0}: {1}")]
+    SliceError(String, SliceError),
+    #[error("Source {0}: {1}")]
+    ImportError(String, ImportError),
 }
 
 impl AssetLoader for TextureSetLoader {
@@ -290,17 +541,15 @@ impl AssetLoader for TextureSetLoader {
         Box::pin(async move {
             let path2 = load_context.path().to_str().unwrap().to_string();
 
-            let mut path = String::new();
-            match path2.rsplit_once('/') {
-                Some(s) => {
-                    path = s.0.to_string() + "/";
-                }
-                None => path = String::from(""),
-            }
+            let path = match path2.rsplit_once('/') {
+                Some(s) => s.0.to_string() + "/",
+                None => String::from(""),
+            };
             let mut bytes = Vec::new();
             reader.read_to_end(&mut bytes).await?;
-            let custom_asset = ron::de::from_bytes::<RonTextureSetAsset>(&bytes)?;
+            let mut custom_asset = ron::de::from_bytes::<RonTextureSetAsset>(&bytes)?;
             let mut pre_src = HashMap::new();
+            let mut imported = TileGroup::new();
 
             for (src_name, cfg) in custom_asset.sources.iter() {
                 match cfg {
@@ -309,29 +558,69 @@ impl AssetLoader for TextureSetLoader {
                         rows,
                         columns,
                         tile_size,
+                        padding,
+                        offset,
+                        indexing,
                     } => {
-                        let img = load_image(load_context, path.to_string() + &source).await?;
+                        let img = load_image(load_context, path.to_string() + source).await?;
+                        let grid = Grid {
+                            padding: *padding,
+                            margin: *offset,
+                            indexing: *indexing,
+                            ..Grid::new(*tile_size, *rows, *columns)
+                        };
+                        grid.check(image_size(&img)).map_err(|err| {
+                            TextureSetLoaderError::SliceError(src_name.clone(), err)
+                        })?;
                         let hdl = load_context
                             .add_labeled_asset(format!("img_{}", src_name), img.clone());
-                        pre_src.insert(
-                            src_name.clone(),
-                            PreSource::TextureAtlas {
-                                img,
-                                hdl,
-                                rows: *rows,
-                                columns: *columns,
-                                tile_size: *tile_size,
-                            },
-                        );
+                        pre_src
+                            .insert(src_name.clone(), PreSource::TextureAtlas { img, hdl, grid });
                     }
                     SourceConfig::Texture { source } => {
-                        let img = load_image(load_context, path.to_string() + &source).await?;
+                        let img = load_image(load_context, path.to_string() + source).await?;
                         let hdl = load_context
                             .add_labeled_asset(format!("img_{}", src_name), img.clone());
                         pre_src.insert(src_name.clone(), PreSource::Texture { img, hdl });
                     }
+                    SourceConfig::Rects { source, rects } => {
+                        let img = load_image(load_context, path.to_string() + source).await?;
+                        let sheet = Sheet::new(rects.clone());
+                        sheet.check(image_size(&img)).map_err(|err| {
+                            TextureSetLoaderError::SliceError(src_name.clone(), err)
+                        })?;
+                        let hdl = load_context
+                            .add_labeled_asset(format!("img_{}", src_name), img.clone());
+                        pre_src.insert(src_name.clone(), PreSource::Rects { img, hdl, sheet });
+                    }
+                    SourceConfig::Json { source } => {
+                        let json = load_context
+                            .read_asset_bytes(AssetPath::from_path(Path::new(
+                                &(path.to_string() + source),
+                            )))
+                            .await?;
+                        let imp = import_json(&json, src_name).map_err(|err| {
+                            TextureSetLoaderError::ImportError(src_name.clone(), err)
+                        })?;
+                        // The image path is relative to the JSON file
+                        let dir = match source.rsplit_once('/') {
+                            Some(s) => s.0.to_string() + "/",
+                            None => String::from(""),
+                        };
+                        let img =
+                            load_image(load_context, path.to_string() + &dir + &imp.image).await?;
+                        let sheet = Sheet::new(imp.rects);
+                        sheet.check(image_size(&img)).map_err(|err| {
+                            TextureSetLoaderError::SliceError(src_name.clone(), err)
+                        })?;
+                        let hdl = load_context
+                            .add_labeled_asset(format!("img_{}", src_name), img.clone());
+                        pre_src.insert(src_name.clone(), PreSource::Rects { img, hdl, sheet });
+                        imported.insert(src_name.clone(), Tile::Group(imp.tiles));
+                    }
                 }
             }
+            merge_tiles(&mut custom_asset.textures, imported);
             let atlas = TextureAtlas::from_grid(
                 load_context.load(DEFAULT_TEXTURE),
                 Vec2::new(16., 16.),
@@ -356,8 +645,26 @@ impl AssetLoader for TextureSetLoader {
     }
 }
 
-async fn load_image<'a>(
-    load_context: &'a mut LoadContext<'_>,
+// Adds imported tiles, tiles written in the set win over them
+fn merge_tiles(textures: &mut TileGroup, imported: TileGroup) {
+    for (name, tile) in imported {
+        match (textures.get_mut(&name), tile) {
+            (Some(Tile::Group(written)), Tile::Group(imported)) => merge_tiles(written, imported),
+            (Some(_), _) => {}
+            (None, tile) => {
+                textures.insert(name, tile);
+            }
+        }
+    }
+}
+
+fn image_size(img: &Image) -> Vec2 {
+    let size = img.texture_descriptor.size;
+    Vec2::new(size.width as f32, size.height as f32)
+}
+
+async fn load_image(
+    load_context: &mut LoadContext<'_>,
     source: String,
 ) -> Result<Image, TextureSetLoaderError> {
     let extension = source.rsplit_once('.').unwrap().1;
@@ -374,36 +681,31 @@ async fn load_image<'a>(
     Ok(image)
 }
 
-fn rects(size: Vec2, rows: u32, columns: u32, offset: Vec2) -> Vec<Rect> {
-    let mut sprites = Vec::new();
-
-    for y in 0..rows {
-        for x in 0..columns {
-            let cell = Vec2::new(x as f32, y as f32);
-
-            let rect_min = (size) * cell + offset;
-
-            sprites.push(Rect {
-                min: rect_min,
-                max: rect_min + size,
-            });
-        }
-    }
-    sprites
-}
-
 #[derive(Clone, Reflect)]
 pub enum TSetTile {
     Single,
     Variant(String),
     Animated(u64),
+    /// Facing of a directional tile, any length
+    Direction(Vec2),
 }
 
+/// Where an entity looks, its [`TSetTile::Direction`] follows it
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq)]
+pub struct Facing(pub Vec2);
+
+// Until the first animation update, one tick of the default rate
+const DEFAULT_TICK_LENGTH: f64 = 1. / 60.;
+
 #[derive(Component, Reflect)]
 pub struct TSetManager {
     pub tset: Handle<TextureSetAsset>,
     tile_name: String,
     data: TSetTile,
+    /// Last frame from [`TSetManager::update_frame`], plays animated directions
+    tick: u64,
+    /// Seconds of one tick, durations in milliseconds are counted with it
+    tick_length: f64,
 }
 
 impl TSetManager {
@@ -412,19 +714,58 @@ impl TSetManager {
             tset,
             tile_name: name.to_string(),
             data,
+            tick: 0,
+            tick_length: DEFAULT_TICK_LENGTH,
         }
     }
     pub fn set_tile(&mut self, name: &str, data: TSetTile) {
         self.tile_name = name.to_string();
         self.data = data;
     }
+    pub fn set_data(&mut self, data: TSetTile) {
+        self.data = data;
+    }
+    pub fn set_tick_length(&mut self, seconds: f64) {
+        self.tick_length = seconds;
+    }
     pub fn update_frame(&mut self, frame: u64) {
-        if let TSetTile::Animated(mframe) = self.data {
+        self.tick = frame;
+        if let TSetTile::Animated(_) = self.data {
             self.data = TSetTile::Animated(frame);
         }
     }
 }
 
+// Plays animations of every tile and turns directional tiles to their facing
+fn animate_tiles(
+    mut query: Query<(&mut TSetManager, Option<Ref<Facing>>)>,
+    tick: Res<crate::Tick>,
+    time: Res<Time<Fixed>>,
+) {
+    let tick_length = time.timestep().as_secs_f64();
+    query.par_iter_mut().for_each(|(mut manager, facing)| {
+        if manager.tick_length != tick_length {
+            manager.set_tick_length(tick_length);
+        }
+        if let Some(facing) = facing {
+            // Zero facing has no direction, the last one is kept
+            if facing.is_changed() && facing.0 != Vec2::ZERO {
+                manager.set_data(TSetTile::Direction(facing.0));
+            }
+        }
+        // Only animations follow the tick, static tiles are not marked changed
+        // and their sprites are not resolved again
+        if manager.tick != tick.0 {
+            match manager.data {
+                TSetTile::Animated(_) | TSetTile::Direction(_) => manager.update_frame(tick.0),
+                TSetTile::Single | TSetTile::Variant(_) => {
+                    manager.bypass_change_detection().update_frame(tick.0)
+                }
+            }
+        }
+    });
+}
+
 fn update(
     mut query: Query<
         (
@@ -435,71 +776,62 @@ fn update(
         Changed<TSetManager>,
     >,
     tsets: Res<Assets<TextureSetAsset>>,
+    atlases: Res<Assets<TextureAtlas>>,
 ) {
     query
         .par_iter_mut()
         .for_each(
             |(manager, mut atlas, mut sprite)| match tsets.get(manager.tset.clone()) {
-                Some(n) => match n.get_tile(&manager.tile_name) {
-                    Tile::Animated { frames, speed } => {
-                        if let TSetTile::Animated(frame) = manager.data {
-                            if !frames.is_empty() {
-                                let (index, atlas2) = n.index_and_atlas(
-                                    frames
-                                        .get((frame as f32 * speed) as usize % frames.len())
-                                        .unwrap()
-                                        .clone(),
-                                );
-                                *atlas = atlas2;
-                                sprite.index = index;
-                            } else {
-                                warn!("Animated tile doesn't have frames! Using default");
-
-                                let (index, atlas2) = n.index_and_atlas(SourceLink::Texture {
-                                    source: "_@default".to_string(),
-                                });
-                                *atlas = atlas2;
-                                sprite.index = index;
-                            }
-                        } else {
-                            warn!("Uncorrect tile!");
-                        }
-                    }
-                    Tile::Single(link) => {
-                        let (index, atlas2) = n.index_and_atlas(link.clone());
-                        *atlas = atlas2;
-                        sprite.index = index;
-                    }
-                    Tile::Group(_map) => {
-                        warn!("Tile is group. Don't use tile. Using default");
-                        let (index, atlas2) = n.index_and_atlas(SourceLink::Texture {
-                            source: "_@default".to_string(),
-                        });
-                        *atlas = atlas2;
-                        sprite.index = index;
-                    }
-                    Tile::Variant { variants } => {
-                        if let TSetTile::Variant(name) = manager.data.clone() {
-                            let variant = match variants.get(&name.clone()) {
-                                Some(var) => var.clone(),
-                                None => {
-                                    warn!("Variant is not exist. Using default");
-                                    SourceLink::Texture {
-                                        source: "_@default".to_string(),
-                                    }
-                                }
-                            };
-                            let (index, atlas2) = n.index_and_atlas(variant.clone());
-                            *atlas = atlas2;
-                            sprite.index = index;
-                        } else {
-                            warn!("Uncorrect tile");
+                Some(n) => {
+                    let frame = n.frame(manager);
+                    let (index, atlas2) = n.index_and_atlas(frame.link);
+                    // The anchor is a fraction of the sprite, the offset is in pixels
+                    let size = atlases
+                        .get(&atlas2)
+                        .and_then(|atlas| atlas.textures.get(index))
+                        .map(|rect| rect.size());
+                    sprite.anchor = match size {
+                        Some(size) if frame.offset != Vec2::ZERO => {
+                            Anchor::Custom(-frame.offset / size)
                         }
-                    }
-                },
+                        _ => Anchor::Center,
+                    };
+                    *atlas = atlas2;
+                    sprite.index = index;
+                    sprite.flip_x = frame.flip_x;
+                    sprite.flip_y = frame.flip_y;
+                }
                 None => {
                     warn!("texture set is not loaded!");
                 }
             },
         );
 }
+
+fn update_ui(
+    mut query: Query<
+        (
+            &TSetManager,
+            &mut Handle<TextureAtlas>,
+            &mut UiTextureAtlasImage,
+        ),
+        Changed<TSetManager>,
+    >,
+    tsets: Res<Assets<TextureSetAsset>>,
+) {
+    for (manager, mut atlas, mut image) in query.iter_mut() {
+        match tsets.get(manager.tset.clone()) {
+            Some(n) => {
+                let frame = n.frame(manager);
+                let (index, atlas2) = n.index_and_atlas(frame.link);
+                *atlas = atlas2;
+                image.index = index;
+                image.flip_x = frame.flip_x;
+                image.flip_y = frame.flip_y;
+            }
+            None => {
+                warn!("texture set is not loaded!");
+            }
+        }
+    }
+}
@@ -1,121 +1,885 @@
-use bevy::prelude::*;
+use bevy::{input::InputSystem, prelude::*, ui::UiSystem};
+use serde::{Deserialize, Serialize};
 
-use crate::settings::*;
+use crate::{settings::*, GameState};
 
 pub struct ControlsPlugin;
 
 impl Plugin for ControlsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_controls)
-            .add_systems(PreUpdate, check_controls)
-            .init_resource::<ControlBinds>()
-            .register_type::<ControlBinds>();
+        // Interactions are updated after input, they tell if the pointer is over the UI
+        app.add_systems(
+            PreUpdate,
+            check_controls
+                .in_set(CheckControls)
+                .after(InputSystem)
+                .after(UiSystem::Focus),
+        )
+        .init_resource::<ControlBinds>()
+        .init_resource::<VirtualInput>()
+        .register_type::<ControlBinds>()
+        .add_systems(
+            OnEnter(GameState::Playing),
+            push_context(InputContext::Gameplay),
+        )
+        .add_systems(
+            OnExit(GameState::Playing),
+            pop_context(InputContext::Gameplay),
+        );
+        for state in [
+            GameState::Menu,
+            GameState::Paused,
+            GameState::Settings,
+            GameState::LoadFailed,
+        ] {
+            app.add_systems(OnEnter(state.clone()), push_context(InputContext::Menu))
+                .add_systems(OnExit(state), pop_context(InputContext::Menu));
+        }
+
+        #[cfg(debug_assertions)]
+        app.add_systems(
+            PostStartup,
+            list_actions.after(crate::settings::persist::load_settings),
+        );
+    }
+}
+
+// Dead zone of the default stick binds
+const STICK_THRESHOLD: f32 = 0.2;
+// Longest pause between two presses of a double tap, in seconds
+const DOUBLE_TAP_TIME: f32 = 0.3;
+// How long an action is held before it counts as a long press, in seconds
+const LONG_PRESS_TIME: f32 = 0.5;
+
+/// Actions of a plugin, usually a fieldless enum registered with [`AddActions::add_actions`]
+pub trait Action: Copy + Send + Sync + 'static {
+    const ALL: &'static [Self];
+    /// Unique name, it is stored in the settings file and shown in the settings screen
+    fn name(&self) -> &'static str;
+    /// Binds used until the player changes them
+    fn default_binds(&self) -> Vec<Chord>;
+    /// Context in which the action reports presses
+    fn context(&self) -> InputContext {
+        InputContext::Gameplay
+    }
+    /// On-screen control of the action, it can't be rebound
+    fn touch_bind(&self) -> Option<TouchBind> {
+        None
+    }
+}
+
+/// Actions are updated in this set, systems feeding [`VirtualInput`] run before it
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CheckControls;
+
+/// Part of the on-screen controls
+#[derive(Clone, Copy, PartialEq, Debug, Reflect)]
+pub enum TouchBind {
+    Up,
+    Down,
+    Left,
+    Right,
+    Button(TouchButton),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Reflect)]
+pub enum TouchButton {
+    Pause,
+}
+
+/// State of the on-screen controls
+#[derive(Resource, Default)]
+pub struct VirtualInput {
+    /// Joystick deflection, each axis from -1 to 1
+    pub stick: Vec2,
+    pub buttons: Input<TouchButton>,
+}
+impl VirtualInput {
+    fn value(&self, bind: TouchBind) -> f32 {
+        match bind {
+            TouchBind::Up => self.stick.y.max(0.),
+            TouchBind::Down => (-self.stick.y).max(0.),
+            TouchBind::Left => (-self.stick.x).max(0.),
+            TouchBind::Right => self.stick.x.max(0.),
+            TouchBind::Button(button) => self.buttons.pressed(button) as u32 as f32,
+        }
+    }
+    fn tapped(&self, bind: TouchBind) -> bool {
+        matches!(bind, TouchBind::Button(button) if self.buttons.just_pressed(button))
+    }
+}
+
+/// Game mode which gives keys their meaning, contexts are pushed to [`ControlBinds`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Reflect)]
+pub enum InputContext {
+    /// Always active, for actions like pause
+    Global,
+    Gameplay,
+    /// Placing buildings, it is pushed over gameplay
+    Build,
+    /// Menus hide every context below them
+    Menu,
+}
+impl InputContext {
+    /// Contexts below it in the stack are inactive
+    fn is_exclusive(&self) -> bool {
+        matches!(self, InputContext::Menu)
+    }
+    fn is_active(&self, stack: &[InputContext]) -> bool {
+        if *self == InputContext::Global {
+            return true;
+        }
+        // Only contexts above the topmost exclusive one are active
+        for context in stack.iter().rev() {
+            if context == self {
+                return true;
+            }
+            if context.is_exclusive() {
+                return false;
+            }
+        }
+        false
+    }
+    /// Actions of both contexts can be active together, so their binds may conflict
+    fn overlaps(&self, other: &InputContext) -> bool {
+        match (self, other) {
+            (InputContext::Global, _) | (_, InputContext::Global) => true,
+            (InputContext::Menu, other) | (other, InputContext::Menu) => {
+                *other == InputContext::Menu
+            }
+            _ => true,
+        }
     }
 }
 
-fn setup_controls(mut binds: ResMut<ControlBinds>) {
-    binds.set("move_up", Bind::Key(KeyCode::W));
-    binds.set("move_down", Bind::Key(KeyCode::S));
-    binds.set("move_left", Bind::Key(KeyCode::A));
-    binds.set("move_right", Bind::Key(KeyCode::D));
+/// Key of an action in [`ControlBinds`], it can be made only from an [`Action`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Reflect)]
+#[reflect_value(Debug, Hash, PartialEq)]
+pub struct ActionId(&'static str);
+impl ActionId {
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+}
+impl<A: Action> From<A> for ActionId {
+    fn from(action: A) -> Self {
+        Self(action.name())
+    }
+}
+
+pub trait AddActions {
+    /// Registers every action of `A` with its default binds
+    fn add_actions<A: Action>(&mut self) -> &mut Self;
+}
+impl AddActions for App {
+    fn add_actions<A: Action>(&mut self) -> &mut Self {
+        self.init_resource::<ControlBinds>();
+        let mut binds = self.world.resource_mut::<ControlBinds>();
+        for action in A::ALL {
+            binds.register(*action);
+        }
+        self
+    }
 }
 
-#[derive(Clone, Copy, Reflect)]
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
 pub enum Bind {
     Key(KeyCode),
     Mouse(MouseButton),
+    GamepadButton(GamepadButtonType),
+    /// Stick or trigger, pressed when deflected past `threshold` in `direction`
+    GamepadAxis {
+        axis: GamepadAxisType,
+        threshold: f32,
+        direction: AxisDirection,
+    },
     None,
 }
+impl Bind {
+    /// Stick direction with the default dead zone
+    pub fn stick(axis: GamepadAxisType, direction: AxisDirection) -> Self {
+        Bind::GamepadAxis {
+            axis,
+            threshold: STICK_THRESHOLD,
+            direction,
+        }
+    }
+}
+impl std::fmt::Display for Bind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bind::Key(code) => write!(f, "{code:?}"),
+            Bind::Mouse(MouseButton::Other(n)) => write!(f, "Mouse {n}"),
+            Bind::Mouse(button) => write!(f, "Mouse {button:?}"),
+            Bind::GamepadButton(GamepadButtonType::Other(n)) => write!(f, "Pad {n}"),
+            Bind::GamepadButton(button) => write!(f, "Pad {button:?}"),
+            Bind::GamepadAxis {
+                axis, direction, ..
+            } => {
+                let sign = match direction {
+                    AxisDirection::Positive => '+',
+                    AxisDirection::Negative => '-',
+                };
+                match axis {
+                    GamepadAxisType::Other(n) => write!(f, "Pad axis {n}{sign}"),
+                    axis => write!(f, "Pad {axis:?}{sign}"),
+                }
+            }
+            Bind::None => write!(f, "-"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+impl AxisDirection {
+    fn sign(&self) -> f32 {
+        match self {
+            AxisDirection::Positive => 1.,
+            AxisDirection::Negative => -1.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Reflect, Serialize, Deserialize)]
+pub enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    Super,
+}
+impl Modifier {
+    pub const ALL: [Modifier; 4] = [
+        Modifier::Ctrl,
+        Modifier::Shift,
+        Modifier::Alt,
+        Modifier::Super,
+    ];
+    /// Left and right keys of the modifier
+    pub fn keys(&self) -> [KeyCode; 2] {
+        match self {
+            Modifier::Ctrl => [KeyCode::ControlLeft, KeyCode::ControlRight],
+            Modifier::Shift => [KeyCode::ShiftLeft, KeyCode::ShiftRight],
+            Modifier::Alt => [KeyCode::AltLeft, KeyCode::AltRight],
+            Modifier::Super => [KeyCode::SuperLeft, KeyCode::SuperRight],
+        }
+    }
+}
+
+/// A bind which is active only while all `modifiers` are held, e.g. Ctrl+S.
+/// Extra modifiers are ignored, W still works with Shift held, unless another active
+/// chord uses them: Ctrl+S takes S from the chord without Ctrl
+#[derive(Clone, PartialEq, Reflect, Serialize, Deserialize)]
+pub struct Chord {
+    #[serde(default)]
+    pub modifiers: Vec<Modifier>,
+    pub bind: Bind,
+}
+impl From<Bind> for Chord {
+    fn from(bind: Bind) -> Self {
+        Self {
+            modifiers: Vec::new(),
+            bind,
+        }
+    }
+}
+impl Chord {
+    /// `other` has the same bind and every modifier of this chord, and more
+    fn is_shadowed_by(&self, other: &Chord) -> bool {
+        self.bind == other.bind
+            && other.modifiers.len() > self.modifiers.len()
+            && self.modifiers.iter().all(|m| other.modifiers.contains(m))
+    }
+}
+impl std::fmt::Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for modifier in self.modifiers.iter() {
+            write!(f, "{modifier:?}+")?;
+        }
+        write!(f, "{}", self.bind)
+    }
+}
+
+/// State of an action in the current frame
+#[derive(Reflect, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ActionState {
+    pub pressed: bool,
+    pub just_pressed: bool,
+    pub just_released: bool,
+    /// Seconds since the action was pressed, 0 while it is released
+    pub held: f32,
+    /// Pressed a second time shortly after the previous press, set only for one frame
+    pub double_tapped: bool,
+    /// Held for [`LONG_PRESS_TIME`], set only in the frame the time is reached
+    pub long_pressed: bool,
+    /// Seconds since the last press which can start a double tap
+    #[serde(default)]
+    since_tap: Option<f32>,
+}
+impl ActionState {
+    /// Moves the state to the next frame, `tapped` is set when a bind was pressed
+    /// during the frame even if it is already released
+    fn update(&mut self, pressed: bool, tapped: bool, delta: f32) {
+        let was_pressed = self.pressed;
+        self.just_pressed = (pressed && !was_pressed) || tapped;
+        self.just_released = !pressed && (was_pressed || self.just_pressed);
+        self.pressed = pressed;
+
+        let held = self.held;
+        self.held = match (self.just_pressed, pressed) {
+            (true, _) | (_, false) => 0.,
+            (false, true) => held + delta,
+        };
+        self.long_pressed = pressed && held < LONG_PRESS_TIME && self.held >= LONG_PRESS_TIME;
+
+        self.since_tap = self.since_tap.map(|time| time + delta);
+        self.double_tapped = false;
+        if self.just_pressed {
+            match self.since_tap {
+                Some(time) if time <= DOUBLE_TAP_TIME => {
+                    self.double_tapped = true;
+                    // A third press starts a new double tap
+                    self.since_tap = None;
+                }
+                _ => self.since_tap = Some(0.),
+            }
+        }
+    }
+}
 
 #[derive(Reflect)]
-pub enum IsRun {
-    Not,
-    ReleaseRun,
-    Run,
-    OnceRun,
+struct Control {
+    context: InputContext,
+    touch: Option<TouchBind>,
+    state: ActionState,
+    /// Reads as released until the binds are released
+    consumed: bool,
+    /// Alternative binds, any of them activates the control
+    binds: Vec<Chord>,
+    /// How strong the control is pressed, from 0 to 1
+    value: f32,
+}
+impl Control {
+    fn new(action: impl Action) -> Self {
+        Self {
+            context: action.context(),
+            touch: action.touch_bind(),
+            state: ActionState::default(),
+            consumed: false,
+            binds: action.default_binds(),
+            value: 0.,
+        }
+    }
 }
 
 #[derive(Resource, Default, Reflect)]
 #[reflect(Resource)]
 pub struct ControlBinds {
-    binds: HashMap<String, (IsRun, Bind)>,
+    binds: HashMap<ActionId, Control>,
+    /// Active contexts, the last one is on top
+    contexts: Vec<InputContext>,
 }
 
 impl ControlBinds {
-    pub fn register(&mut self, name: &str) {
-        self.binds
-            .insert(String::from(name), (IsRun::Not, Bind::None));
+    pub fn register(&mut self, action: impl Action) {
+        if self.binds.contains_key(&ActionId::from(action)) {
+            warn!("Action {} is registered twice", action.name());
+        }
+        self.binds.insert(action.into(), Control::new(action));
     }
-    pub fn set(&mut self, name: &str, bind: Bind) {
-        match self.binds.get_mut(name) {
-            Some((_, s)) => {
-                *s = bind;
-            }
-            None => {
-                self.binds.insert(String::from(name), (IsRun::Not, bind));
-                info!("Control is not found, register it: {name}");
+    pub fn push_context(&mut self, context: InputContext) {
+        self.contexts.push(context);
+    }
+    /// Removes the topmost `context`, contexts above it stay
+    pub fn pop_context(&mut self, context: InputContext) {
+        match self.contexts.iter().rposition(|c| *c == context) {
+            Some(i) => {
+                self.contexts.remove(i);
             }
+            None => warn!("Input context {context:?} is popped but it is not active"),
         }
     }
-    pub fn is_pressed(&self, name: &str) -> bool {
-        match self.binds.get(name) {
-            Some((is, _s)) => !matches!(is, IsRun::Not),
-            None => {
-                warn!("Control is not registered. Register it!");
-                false
-            }
+    pub fn is_active(&self, context: InputContext) -> bool {
+        context.is_active(&self.contexts)
+    }
+    /// Marks the action as handled, it reads as released until its binds are released
+    pub fn consume(&mut self, action: impl Into<ActionId>) {
+        if let Some(control) = self.control_mut(action.into()) {
+            control.consumed = true;
+            control.state = ActionState::default();
+            control.value = 0.;
         }
     }
-    pub fn is_just_pressed(&self, name: &str) -> bool {
-        match self.binds.get(name) {
-            Some((is, _s)) => matches!(is, IsRun::OnceRun),
-            None => {
-                warn!("Control is not registered. Register it!");
-                false
-            }
+    /// Registered action with the name
+    pub fn find(&self, name: &str) -> Option<ActionId> {
+        self.binds.keys().find(|id| id.name() == name).copied()
+    }
+    /// Replaces all binds of the action with `chord`
+    pub fn set(&mut self, action: impl Into<ActionId>, chord: impl Into<Chord>) {
+        self.set_all(action, vec![chord.into()]);
+    }
+    pub fn set_all(&mut self, action: impl Into<ActionId>, chords: Vec<Chord>) {
+        if let Some(control) = self.control_mut(action.into()) {
+            control.binds = chords;
         }
     }
-    pub fn is_just_released(&self, name: &str) -> bool {
-        match self.binds.get(name) {
-            Some((is, _s)) => matches!(is, IsRun::ReleaseRun),
-            None => {
-                warn!("Control is not registered. Register it!");
-                false
+    /// Removes one bind of the action, other alternatives stay
+    pub fn remove(&mut self, action: impl Into<ActionId>, chord: &Chord) {
+        if let Some(control) = self.control_mut(action.into()) {
+            control.binds.retain(|other| other != chord);
+        }
+    }
+    /// Adds an alternative bind to the action
+    pub fn add(&mut self, action: impl Into<ActionId>, chord: impl Into<Chord>) {
+        let chord = chord.into();
+        if let Some(control) = self.control_mut(action.into()) {
+            if !control.binds.contains(&chord) {
+                control.binds.push(chord);
             }
         }
     }
+    pub fn get(&self, action: impl Into<ActionId>) -> &[Chord] {
+        match self.control(action.into()) {
+            Some(control) => control.binds.as_slice(),
+            None => &[],
+        }
+    }
+    /// Registered actions and their binds, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (ActionId, &[Chord])> {
+        self.binds
+            .iter()
+            .map(|(id, control)| (*id, control.binds.as_slice()))
+    }
+    /// Other actions which share a bind with `action` and can be active with it
+    pub fn conflicts(&self, action: impl Into<ActionId>) -> Vec<ActionId> {
+        let action = action.into();
+        let chords = self.get(action);
+        let context = self.control(action).map(|control| control.context);
+        self.iter()
+            .filter(|(other, other_chords)| {
+                *other != action
+                    && context.is_some_and(|context| context.overlaps(&self.binds[other].context))
+                    && other_chords
+                        .iter()
+                        .any(|chord| chord.bind != Bind::None && chords.contains(chord))
+            })
+            .map(|(other, _)| other)
+            .collect()
+    }
+    /// Any of the binds is held
+    pub fn is_pressed(&self, action: impl Into<ActionId>) -> bool {
+        self.state(action).pressed
+    }
+    pub fn is_just_pressed(&self, action: impl Into<ActionId>) -> bool {
+        self.state(action).just_pressed
+    }
+    pub fn is_just_released(&self, action: impl Into<ActionId>) -> bool {
+        self.state(action).just_released
+    }
+    /// Seconds the action is held, 0 while it is released
+    pub fn held_for(&self, action: impl Into<ActionId>) -> f32 {
+        self.state(action).held
+    }
+    pub fn is_double_tapped(&self, action: impl Into<ActionId>) -> bool {
+        self.state(action).double_tapped
+    }
+    pub fn is_long_pressed(&self, action: impl Into<ActionId>) -> bool {
+        self.state(action).long_pressed
+    }
+    /// Full state of the action, released if it is not registered
+    pub fn state(&self, action: impl Into<ActionId>) -> ActionState {
+        self.control(action.into())
+            .map_or_else(ActionState::default, |control| control.state)
+    }
+    /// Overrides the state until the next update, used to replay recorded input
+    pub fn set_state(&mut self, action: impl Into<ActionId>, state: ActionState, value: f32) {
+        if let Some(control) = self.control_mut(action.into()) {
+            control.state = state;
+            control.value = value;
+        }
+    }
+    pub fn context(&self, action: impl Into<ActionId>) -> Option<InputContext> {
+        self.control(action.into()).map(|control| control.context)
+    }
+    /// Analog value of the action from 0 to 1, digital binds give 0 or 1
+    pub fn value(&self, action: impl Into<ActionId>) -> f32 {
+        self.control(action.into())
+            .map_or(0., |control| control.value)
+    }
+
+    // Only an action of a plugin which forgot to call `add_actions` is missing
+    fn control(&self, id: ActionId) -> Option<&Control> {
+        let control = self.binds.get(&id);
+        if control.is_none() {
+            warn!(
+                "Action {} is not registered, register it with add_actions",
+                id.name()
+            );
+        }
+        control
+    }
+    fn control_mut(&mut self, id: ActionId) -> Option<&mut Control> {
+        let control = self.binds.get_mut(&id);
+        if control.is_none() {
+            warn!(
+                "Action {} is not registered, register it with add_actions",
+                id.name()
+            );
+        }
+        control
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn check_controls(
     mut binds: ResMut<ControlBinds>,
     keys: Res<Input<KeyCode>>,
     buttons: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_button_axes: Res<Axis<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    time: Res<Time<Real>>,
+    interactions: Query<&Interaction>,
+    touch: Res<VirtualInput>,
 ) {
-    for (_k, (i2, i)) in binds.binds.iter_mut() {
-        match i {
-            Bind::Key(code) => {
-                if keys.just_pressed(*code) {
-                    *i2 = IsRun::OnceRun;
-                } else if keys.pressed(*code) {
-                    *i2 = IsRun::Run;
-                } else if keys.just_released(*code) {
-                    *i2 = IsRun::ReleaseRun;
-                } else {
-                    *i2 = IsRun::Not;
-                }
-            }
-            Bind::Mouse(code) => {
-                if buttons.just_pressed(*code) {
-                    *i2 = IsRun::OnceRun;
-                } else if buttons.pressed(*code) {
-                    *i2 = IsRun::Run;
-                } else if buttons.just_released(*code) {
-                    *i2 = IsRun::ReleaseRun;
+    // Clicks on the UI are consumed by it, only menu actions see the mouse then
+    let pointer_on_ui = interactions
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+
+    // Value of a single bind, every connected gamepad controls the game
+    let bind_value = |bind: &Bind| -> f32 {
+        match *bind {
+            Bind::Key(code) => keys.pressed(code) as u32 as f32,
+            Bind::Mouse(code) => buttons.pressed(code) as u32 as f32,
+            Bind::GamepadButton(button) => gamepads
+                .iter()
+                .map(|gamepad| GamepadButton::new(gamepad, button))
+                // The press thresholds of the gamepad settings decide if a trigger is pulled,
+                // a trigger at rest or drifting doesn't press the action
+                .filter(|button| gamepad_buttons.pressed(*button))
+                // Triggers report how far they are pulled, other buttons are 1
+                .map(|button| {
+                    gamepad_button_axes
+                        .get(button)
+                        .filter(|value| *value > 0.)
+                        .unwrap_or(1.)
+                })
+                .fold(0., f32::max),
+            Bind::GamepadAxis {
+                axis,
+                threshold,
+                direction,
+            } => {
+                let deflection = gamepads
+                    .iter()
+                    .filter_map(|gamepad| gamepad_axes.get(GamepadAxis::new(gamepad, axis)))
+                    .map(|value| value * direction.sign())
+                    .fold(0., f32::max);
+                if deflection >= threshold {
+                    deflection.min(1.)
                 } else {
-                    *i2 = IsRun::Not;
+                    0.
                 }
             }
-            _ => {}
+            Bind::None => 0.,
+        }
+    };
+    // Digital binds which went down this frame, axes only change between frames
+    let bind_tapped = |bind: &Bind| -> bool {
+        match *bind {
+            Bind::Key(code) => keys.just_pressed(code),
+            Bind::Mouse(code) => buttons.just_pressed(code),
+            Bind::GamepadButton(button) => gamepads
+                .iter()
+                .any(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button))),
+            Bind::GamepadAxis { .. } | Bind::None => false,
+        }
+    };
+    let modifiers_held = |chord: &Chord| {
+        chord
+            .modifiers
+            .iter()
+            .all(|modifier| keys.any_pressed(modifier.keys()))
+    };
+
+    // Input states change every frame, only edits of the binds mark the resource changed
+    let ControlBinds { binds, contexts } = binds.bypass_change_detection();
+    // Chords which may take the bind from less specific ones
+    let held: Vec<Chord> = binds
+        .values()
+        .filter(|control| control.context.is_active(contexts))
+        .flat_map(|control| control.binds.iter())
+        .filter(|chord| !chord.modifiers.is_empty() && modifiers_held(chord))
+        .cloned()
+        .collect();
+    for (_k, control) in binds.iter_mut() {
+        // Actions of inactive contexts read as released
+        let active = control.context.is_active(contexts);
+        let mouse_free = control.context == InputContext::Menu || !pointer_on_ui;
+        let chords: Vec<_> = control
+            .binds
+            .iter()
+            .filter(|chord| active && (mouse_free || !matches!(chord.bind, Bind::Mouse(_))))
+            .filter(|chord| modifiers_held(chord))
+            .filter(|chord| !held.iter().any(|other| chord.is_shadowed_by(other)))
+            .collect();
+        let touch_bind = control.touch.filter(|_| active);
+        let value = chords
+            .iter()
+            .map(|chord| bind_value(&chord.bind))
+            .chain(touch_bind.map(|bind| touch.value(bind)))
+            .fold(0., f32::max);
+        // Catches binds pressed and released between two frames
+        let tapped = chords.iter().any(|chord| bind_tapped(&chord.bind))
+            || touch_bind.is_some_and(|bind| touch.tapped(bind));
+        if control.consumed {
+            control.consumed = value > 0.;
+            continue;
+        }
+        control
+            .state
+            .update(value > 0., tapped, time.delta_seconds());
+        control.value = value;
+    }
+}
+
+fn push_context(context: InputContext) -> impl Fn(ResMut<ControlBinds>) {
+    move |mut binds| binds.push_context(context)
+}
+
+fn pop_context(context: InputContext) -> impl Fn(ResMut<ControlBinds>) {
+    move |mut binds| binds.pop_context(context)
+}
+
+#[cfg(debug_assertions)]
+fn list_actions(binds: Res<ControlBinds>) {
+    let mut actions: Vec<_> = binds
+        .iter()
+        .map(|(id, chords)| {
+            let chords: Vec<_> = chords.iter().map(|chord| chord.to_string()).collect();
+            format!("{}: {}", id.name(), chords.join(", "))
+        })
+        .collect();
+    actions.sort();
+    info!("Registered actions:\n{}", actions.join("\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy)]
+    struct Up;
+    impl Action for Up {
+        const ALL: &'static [Self] = &[Up];
+        fn name(&self) -> &'static str {
+            "up"
+        }
+        fn default_binds(&self) -> Vec<Chord> {
+            vec![Bind::Key(KeyCode::W).into(), Bind::Key(KeyCode::Up).into()]
+        }
+        fn context(&self) -> InputContext {
+            InputContext::Global
+        }
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Input<KeyCode>>()
+            .init_resource::<Input<MouseButton>>()
+            .init_resource::<Gamepads>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<Axis<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .init_resource::<Time<Real>>()
+            .init_resource::<VirtualInput>()
+            .add_actions::<Up>()
+            .add_systems(Update, check_controls);
+        // The first time update only starts the clock
+        app.world.resource_mut::<Time<Real>>().update();
+        app
+    }
+
+    // Runs a frame of `seconds` in which `pressed` keys go down and `released` go up
+    fn frame(app: &mut App, seconds: f32, pressed: &[KeyCode], released: &[KeyCode]) {
+        let mut keys = app.world.resource_mut::<Input<KeyCode>>();
+        keys.clear();
+        pressed.iter().for_each(|key| keys.press(*key));
+        released.iter().for_each(|key| keys.release(*key));
+        app.world
+            .resource_mut::<Time<Real>>()
+            .update_with_duration(std::time::Duration::from_secs_f32(seconds));
+        app.update();
+    }
+
+    fn binds(app: &App) -> &ControlBinds {
+        app.world.resource::<ControlBinds>()
+    }
+
+    #[test]
+    fn any_alternative_presses() {
+        for key in [KeyCode::W, KeyCode::Up] {
+            let mut app = app();
+            frame(&mut app, 0.1, &[], &[]);
+            assert!(!binds(&app).is_pressed(Up));
+            frame(&mut app, 0.1, &[key], &[]);
+            assert!(binds(&app).is_pressed(Up));
+            assert!(binds(&app).is_just_pressed(Up));
+            frame(&mut app, 0.1, &[], &[key]);
+            assert!(!binds(&app).is_pressed(Up));
+            assert!(binds(&app).is_just_released(Up));
+        }
+    }
+
+    #[test]
+    fn removed_bind_leaves_the_others() {
+        let mut app = app();
+        app.world
+            .resource_mut::<ControlBinds>()
+            .remove(Up, &Chord::from(Bind::Key(KeyCode::W)));
+        frame(&mut app, 0.1, &[KeyCode::W], &[]);
+        assert!(!binds(&app).is_pressed(Up));
+        frame(&mut app, 0.1, &[KeyCode::Up], &[KeyCode::W]);
+        assert!(binds(&app).is_pressed(Up));
+    }
+
+    #[test]
+    fn input_alone_does_not_change_binds() {
+        #[derive(Resource, Default)]
+        struct Changed(bool);
+        let mut app = app();
+        app.init_resource::<Changed>().add_systems(
+            Last,
+            |binds: Res<ControlBinds>, mut changed: ResMut<Changed>| changed.0 = binds.is_changed(),
+        );
+        frame(&mut app, 0.1, &[], &[]);
+        frame(&mut app, 0.1, &[KeyCode::W], &[]);
+        assert!(binds(&app).is_pressed(Up));
+        assert!(!app.world.resource::<Changed>().0);
+        app.world
+            .resource_mut::<ControlBinds>()
+            .set(Up, Bind::Key(KeyCode::S));
+        frame(&mut app, 0.1, &[], &[]);
+        assert!(app.world.resource::<Changed>().0);
+    }
+
+    #[test]
+    fn hold_time_and_long_press() {
+        let mut app = app();
+        frame(&mut app, 0.1, &[KeyCode::W], &[]);
+        assert_eq!(binds(&app).held_for(Up), 0.);
+        frame(&mut app, 0.25, &[], &[]);
+        assert_eq!(binds(&app).held_for(Up), 0.25);
+        assert!(!binds(&app).is_long_pressed(Up));
+        frame(&mut app, 0.25, &[], &[]);
+        assert!(binds(&app).is_long_pressed(Up));
+        // Long press is reported once
+        frame(&mut app, 0.25, &[], &[]);
+        assert!(!binds(&app).is_long_pressed(Up));
+        frame(&mut app, 0.1, &[], &[KeyCode::W]);
+        assert_eq!(binds(&app).held_for(Up), 0.);
+    }
+
+    #[test]
+    fn double_tap() {
+        let mut app = app();
+        frame(&mut app, 0.1, &[KeyCode::W], &[]);
+        frame(&mut app, 0.1, &[], &[KeyCode::W]);
+        assert!(!binds(&app).is_double_tapped(Up));
+        // Presses too far apart are two single taps
+        frame(&mut app, 1., &[KeyCode::W], &[]);
+        assert!(!binds(&app).is_double_tapped(Up));
+        frame(&mut app, 0.1, &[], &[KeyCode::W]);
+        // Any alternative counts as the second press
+        frame(&mut app, 0.1, &[KeyCode::Up], &[]);
+        assert!(binds(&app).is_double_tapped(Up));
+        frame(&mut app, 0.1, &[], &[KeyCode::Up]);
+        assert!(!binds(&app).is_double_tapped(Up));
+    }
+
+    #[test]
+    fn contexts_push_and_pop() {
+        let mut binds = ControlBinds::default();
+        assert!(binds.is_active(InputContext::Global));
+        assert!(!binds.is_active(InputContext::Gameplay));
+        binds.push_context(InputContext::Gameplay);
+        binds.push_context(InputContext::Build);
+        assert!(binds.is_active(InputContext::Gameplay));
+        assert!(binds.is_active(InputContext::Build));
+        // A menu hides the world below it
+        binds.push_context(InputContext::Menu);
+        assert!(!binds.is_active(InputContext::Build));
+        binds.pop_context(InputContext::Menu);
+        binds.pop_context(InputContext::Build);
+        assert!(!binds.is_active(InputContext::Build));
+        assert!(binds.is_active(InputContext::Gameplay));
+    }
+
+    #[test]
+    fn trigger_presses_past_gamepad_threshold() {
+        use bevy::input::gamepad::*;
+        let mut app = app();
+        app.add_event::<GamepadConnectionEvent>()
+            .add_systems(PreUpdate, gamepad_connection_system);
+        let gamepad = Gamepad::new(0);
+        app.world.send_event(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected(GamepadInfo {
+                name: "pad".to_string(),
+            }),
+        ));
+        let trigger = GamepadButton::new(gamepad, GamepadButtonType::RightTrigger2);
+        app.world
+            .resource_mut::<ControlBinds>()
+            .set(Up, Bind::GamepadButton(GamepadButtonType::RightTrigger2));
+        frame(&mut app, 0.1, &[], &[]);
+
+        // Resting or drifting trigger below the press threshold
+        app.world
+            .resource_mut::<Axis<GamepadButton>>()
+            .set(trigger, 0.1);
+        frame(&mut app, 0.1, &[], &[]);
+        assert!(!binds(&app).is_pressed(Up));
+        assert_eq!(binds(&app).value(Up), 0.);
+
+        app.world
+            .resource_mut::<Axis<GamepadButton>>()
+            .set(trigger, 0.7);
+        app.world
+            .resource_mut::<Input<GamepadButton>>()
+            .press(trigger);
+        frame(&mut app, 0.1, &[], &[]);
+        assert!(binds(&app).is_pressed(Up));
+        assert_eq!(binds(&app).value(Up), 0.7);
+    }
+
+    fn chord(modifiers: &[Modifier], key: KeyCode) -> Chord {
+        Chord {
+            modifiers: modifiers.to_vec(),
+            bind: Bind::Key(key),
         }
     }
+
+    #[test]
+    fn most_specific_chord_wins() {
+        let s = chord(&[], KeyCode::S);
+        let ctrl_s = chord(&[Modifier::Ctrl], KeyCode::S);
+        let ctrl_shift_s = chord(&[Modifier::Ctrl, Modifier::Shift], KeyCode::S);
+        assert!(s.is_shadowed_by(&ctrl_s));
+        assert!(ctrl_s.is_shadowed_by(&ctrl_shift_s));
+        assert!(!ctrl_s.is_shadowed_by(&s));
+        assert!(!ctrl_s.is_shadowed_by(&ctrl_s));
+    }
+
+    #[test]
+    fn other_binds_and_modifiers_do_not_shadow() {
+        let ctrl_s = chord(&[Modifier::Ctrl], KeyCode::S);
+        assert!(!chord(&[], KeyCode::W).is_shadowed_by(&ctrl_s));
+        assert!(!chord(&[Modifier::Shift], KeyCode::S).is_shadowed_by(&ctrl_s));
+    }
 }
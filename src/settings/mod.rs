@@ -1,6 +1,11 @@
 use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::GameState;
 
 pub mod controls;
+mod persist;
+mod ui;
 
 pub struct SettingsPlugin;
 
@@ -8,7 +13,19 @@ impl Plugin for SettingsPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(controls::ControlsPlugin)
             .init_resource::<ScaleSize>()
-            .register_type::<ScaleSize>();
+            .init_resource::<Volume>()
+            .init_resource::<SettingsReturn>()
+            .register_type::<ScaleSize>()
+            .register_type::<Volume>();
+    }
+}
+
+/// Settings screen and the settings file, headless runs keep the defaults
+pub struct SettingsRenderPlugin;
+
+impl Plugin for SettingsRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((ui::SettingsUiPlugin, persist::PersistPlugin));
     }
 }
 
@@ -20,3 +37,30 @@ impl Default for ScaleSize {
         Self(5.)
     }
 }
+
+/// Volume multipliers from 0 to 1, a channel plays at `master * channel`
+#[derive(Reflect, Resource, Clone, Copy, Serialize, Deserialize)]
+#[reflect(Default, Resource)]
+pub struct Volume {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+impl Default for Volume {
+    fn default() -> Self {
+        Self {
+            master: 1.,
+            music: 0.5,
+            sfx: 1.,
+        }
+    }
+}
+
+/// State to go back to when the settings screen is closed
+#[derive(Resource)]
+pub(crate) struct SettingsReturn(pub GameState);
+impl Default for SettingsReturn {
+    fn default() -> Self {
+        Self(GameState::Menu)
+    }
+}
@@ -0,0 +1,142 @@
+use bevy::{
+    prelude::*,
+    utils::HashMap,
+    window::{PrimaryWindow, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{controls::*, ui::SliderSetting, ScaleSize, Volume};
+use crate::GameState;
+
+const SETTINGS_FILE: &str = "settings.ron";
+
+pub struct PersistPlugin;
+
+impl Plugin for PersistPlugin {
+    fn build(&self, app: &mut App) {
+        // Actions are registered while plugins are built, saved binds are applied over them
+        app.add_systems(PostStartup, load_settings)
+            .add_systems(OnExit(GameState::Settings), save_settings);
+    }
+}
+
+/// Everything in the settings file is optional, missing values keep their defaults
+#[derive(Serialize, Deserialize, Default)]
+struct SettingsFile {
+    #[serde(default)]
+    binds: HashMap<String, Vec<Chord>>,
+    #[serde(default)]
+    scale_size: Option<f32>,
+    #[serde(default)]
+    window_mode: Option<WindowMode>,
+    #[serde(default)]
+    volume: Option<Volume>,
+}
+
+// logic
+pub(super) fn load_settings(
+    mut binds: ResMut<ControlBinds>,
+    mut scale: ResMut<ScaleSize>,
+    mut volume: ResMut<Volume>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Some(data) = read() else {
+        info!("Settings file is not found, using defaults");
+        return;
+    };
+    let file = match ron::de::from_str::<SettingsFile>(&data) {
+        Ok(file) => file,
+        Err(e) => {
+            warn!("Failed to parse settings file, using defaults: {e}");
+            return;
+        }
+    };
+    for (name, chords) in file.binds {
+        // Actions which are not registered anymore are dropped on the next save
+        if let Some(action) = binds.find(&name) {
+            binds.set_all(action, chords);
+        }
+    }
+    if let Some(scale_size) = file.scale_size {
+        scale.0 = SliderSetting::ZoomSensitivity.clamp(scale_size);
+    }
+    // A hand edited file may hold values the sliders can't show
+    if let Some(file_volume) = file.volume {
+        *volume = Volume {
+            master: SliderSetting::MasterVolume.clamp(file_volume.master),
+            music: SliderSetting::MusicVolume.clamp(file_volume.music),
+            sfx: SliderSetting::SfxVolume.clamp(file_volume.sfx),
+        };
+    }
+    if let (Some(mode), Ok(mut window)) = (file.window_mode, window.get_single_mut()) {
+        window.mode = mode;
+    }
+}
+
+fn save_settings(
+    binds: Res<ControlBinds>,
+    scale: Res<ScaleSize>,
+    volume: Res<Volume>,
+    window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let file = SettingsFile {
+        binds: binds
+            .iter()
+            .map(|(action, chords)| (action.name().to_string(), chords.to_vec()))
+            .collect(),
+        scale_size: Some(scale.0),
+        window_mode: window.get_single().ok().map(|window| window.mode),
+        volume: Some(*volume),
+    };
+    match ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default()) {
+        Ok(data) => write(&data),
+        Err(e) => warn!("Failed to serialize settings: {e}"),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn path() -> Option<std::path::PathBuf> {
+    let dirs = directories::ProjectDirs::from("me", "fidius", "Miner")?;
+    Some(dirs.config_dir().join(SETTINGS_FILE))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read() -> Option<String> {
+    std::fs::read_to_string(path()?).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(data: &str) {
+    let Some(path) = path() else {
+        warn!("Config directory is not found, settings are not saved");
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            warn!("Failed to create config directory: {e}");
+            return;
+        }
+    }
+    match std::fs::write(&path, data) {
+        Ok(()) => info!("Settings are saved to {}", path.display()),
+        Err(e) => warn!("Failed to save settings: {e}"),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read() -> Option<String> {
+    storage()?.get_item(SETTINGS_FILE).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(data: &str) {
+    match storage().map(|storage| storage.set_item(SETTINGS_FILE, data)) {
+        Some(Ok(())) => info!("Settings are saved to local storage"),
+        _ => warn!("Failed to save settings to local storage"),
+    }
+}
@@ -0,0 +1,643 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    window::PrimaryWindow,
+    window::WindowMode,
+};
+
+use super::{controls::*, ScaleSize, SettingsReturn, Volume};
+use crate::menu::{MenuAction, BUTTON_COLOR, FOCUSED_COLOR, MENU_BACKGROUND, TEXT_COLOR};
+use crate::{GameState, FONT};
+
+const CAPTURE_COLOR: Color = Color::rgb(0.8, 0.7, 0.2);
+const CONFLICT_COLOR: Color = Color::rgb(0.9, 0.3, 0.3);
+const SLIDER_FILL: Color = Color::rgb(0.5, 0.5, 0.6);
+const FONT_SIZE: f32 = 24.;
+// How far a stick has to be moved to be captured
+const AXIS_CAPTURE: f32 = 0.7;
+// Dead zone of captured axis binds
+const AXIS_THRESHOLD: f32 = 0.3;
+// Height of the bind list, the rest is scrolled
+const BIND_LIST_HEIGHT: f32 = 360.;
+// Pixels scrolled per line of a mouse wheel
+const SCROLL_LINE: f32 = 42.;
+
+pub struct SettingsUiPlugin;
+
+impl Plugin for SettingsUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Capture>()
+            .add_systems(OnEnter(GameState::Settings), setup_settings)
+            .add_systems(OnExit(GameState::Settings), despawn_settings)
+            .add_systems(
+                Update,
+                (
+                    click_buttons,
+                    capture_bind,
+                    drag_sliders,
+                    scroll_binds,
+                    update_binds,
+                    update_sliders,
+                    update_labels,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Settings)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct SettingsScreen;
+
+/// Action which waits for a new bind
+#[derive(Resource, Default)]
+struct Capture(Option<Rebind>);
+
+#[derive(Clone)]
+struct Rebind {
+    action: ActionId,
+    mode: RebindMode,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RebindMode {
+    /// Replaces all binds of the action
+    Set,
+    /// Adds the bind as an alternative
+    Add,
+    /// Removes the pressed bind, other alternatives stay
+    Remove,
+}
+
+#[derive(Component)]
+enum SettingsButton {
+    /// Replaces binds of the action
+    Bind(ActionId),
+    /// Adds an alternative bind to the action
+    AddBind(ActionId),
+    /// Removes one bind of the action
+    RemoveBind(ActionId),
+    Fullscreen,
+    Back,
+}
+
+#[derive(Component, Clone, Copy)]
+pub(super) enum SliderSetting {
+    ZoomSensitivity,
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+}
+impl SliderSetting {
+    const ALL: [SliderSetting; 4] = [
+        SliderSetting::ZoomSensitivity,
+        SliderSetting::MasterVolume,
+        SliderSetting::MusicVolume,
+        SliderSetting::SfxVolume,
+    ];
+    fn label(&self) -> &'static str {
+        match self {
+            SliderSetting::ZoomSensitivity => "Zoom sensitivity",
+            SliderSetting::MasterVolume => "Volume",
+            SliderSetting::MusicVolume => "Music",
+            SliderSetting::SfxVolume => "Sounds",
+        }
+    }
+    fn range(&self) -> (f32, f32) {
+        match self {
+            SliderSetting::ZoomSensitivity => (1., 20.),
+            _ => (0., 1.),
+        }
+    }
+    /// Keeps a value, e.g. from the settings file, inside the slider
+    pub(super) fn clamp(&self, value: f32) -> f32 {
+        let (min, max) = self.range();
+        value.clamp(min, max)
+    }
+    fn format(&self, value: f32) -> String {
+        match self {
+            SliderSetting::ZoomSensitivity => format!("{value:.1}"),
+            _ => format!("{:.0}%", value * 100.),
+        }
+    }
+}
+
+#[derive(Component)]
+struct SliderFill(SliderSetting);
+
+#[derive(Component)]
+struct SliderValue(SliderSetting);
+
+#[derive(Component)]
+struct FullscreenLabel;
+
+#[derive(Component)]
+struct ConflictText;
+
+/// Rows of the actions, moved inside a clipped panel
+#[derive(Component, Default)]
+struct BindList {
+    offset: f32,
+}
+
+// logic
+fn setup_settings(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    binds: Res<ControlBinds>,
+) {
+    let font = asset_server.load(FONT);
+    let text = |value: &str| {
+        TextBundle::from_section(
+            value,
+            TextStyle {
+                font: font.clone(),
+                font_size: FONT_SIZE,
+                color: TEXT_COLOR,
+            },
+        )
+    };
+    let row = || NodeBundle {
+        style: Style {
+            width: Val::Px(600.),
+            justify_content: JustifyContent::SpaceBetween,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let button = |width: f32| ButtonBundle {
+        style: Style {
+            width: Val::Px(width),
+            height: Val::Px(36.),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        background_color: BUTTON_COLOR.into(),
+        ..Default::default()
+    };
+
+    let mut actions: Vec<_> = binds.iter().map(|(action, _)| action).collect();
+    actions.sort_by_key(|action| action.name());
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(6.),
+                    ..Default::default()
+                },
+                background_color: MENU_BACKGROUND.into(),
+                z_index: ZIndex::Global(10),
+                ..Default::default()
+            },
+            Name::new("Settings"),
+            SettingsScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(text("Controls"));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        max_height: Val::Px(BIND_LIST_HEIGHT),
+                        overflow: Overflow::clip_y(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn((
+                            NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Column,
+                                    row_gap: Val::Px(6.),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            BindList::default(),
+                        ))
+                        .with_children(|parent| {
+                            for action in actions {
+                                parent.spawn(row()).with_children(|parent| {
+                                    parent.spawn(text(action.name()));
+                                    parent
+                                        .spawn(NodeBundle {
+                                            style: Style {
+                                                column_gap: Val::Px(6.),
+                                                ..Default::default()
+                                            },
+                                            ..Default::default()
+                                        })
+                                        .with_children(|parent| {
+                                            parent
+                                                .spawn((button(300.), SettingsButton::Bind(action)))
+                                                .with_children(|parent| {
+                                                    parent.spawn(text(""));
+                                                });
+                                            parent
+                                                .spawn((
+                                                    button(36.),
+                                                    SettingsButton::AddBind(action),
+                                                ))
+                                                .with_children(|parent| {
+                                                    parent.spawn(text("+"));
+                                                });
+                                            parent
+                                                .spawn((
+                                                    button(36.),
+                                                    SettingsButton::RemoveBind(action),
+                                                ))
+                                                .with_children(|parent| {
+                                                    parent.spawn(text("-"));
+                                                });
+                                        });
+                                });
+                            }
+                        });
+                });
+            parent.spawn((text(""), ConflictText));
+
+            for setting in SliderSetting::ALL {
+                parent.spawn(row()).with_children(|parent| {
+                    parent.spawn(text(setting.label()));
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(160.),
+                                    height: Val::Px(16.),
+                                    ..Default::default()
+                                },
+                                background_color: BUTTON_COLOR.into(),
+                                ..Default::default()
+                            },
+                            setting,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                NodeBundle {
+                                    style: Style {
+                                        height: Val::Percent(100.),
+                                        ..Default::default()
+                                    },
+                                    background_color: SLIDER_FILL.into(),
+                                    focus_policy: bevy::ui::FocusPolicy::Pass,
+                                    ..Default::default()
+                                },
+                                SliderFill(setting),
+                            ));
+                        });
+                    parent.spawn((text(""), SliderValue(setting)));
+                });
+            }
+
+            parent.spawn(row()).with_children(|parent| {
+                parent.spawn(text("Fullscreen"));
+                parent
+                    .spawn((button(200.), SettingsButton::Fullscreen))
+                    .with_children(|parent| {
+                        parent.spawn((text(""), FullscreenLabel));
+                    });
+            });
+
+            parent
+                .spawn((button(260.), SettingsButton::Back))
+                .with_children(|parent| {
+                    parent.spawn(text("Back"));
+                });
+        });
+}
+
+fn despawn_settings(
+    mut commands: Commands,
+    query: Query<Entity, With<SettingsScreen>>,
+    mut capture: ResMut<Capture>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    capture.0 = None;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn capture_bind(
+    mut capture: ResMut<Capture>,
+    mut binds: ResMut<ControlBinds>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    back: Res<SettingsReturn>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(rebind) = capture.0.clone() else {
+        if binds.is_just_pressed(MenuAction::Pause) {
+            next_state.set(back.0.clone());
+        }
+        return;
+    };
+    // The capture was started this frame, the click which started it is not a bind
+    if capture.is_changed() {
+        return;
+    }
+    let is_modifier = |key: &KeyCode| {
+        Modifier::ALL
+            .iter()
+            .any(|modifier| modifier.keys().contains(key))
+    };
+    let mut modifiers: Vec<_> = Modifier::ALL
+        .into_iter()
+        .filter(|modifier| keys.any_pressed(modifier.keys()))
+        .collect();
+    // Modifiers wait for the main key, but a modifier pressed and released alone is a bind itself
+    let key = keys
+        .get_just_pressed()
+        .find(|key| !is_modifier(key))
+        .or_else(|| keys.get_just_released().find(|key| is_modifier(key)));
+    let bind = if let Some(key) = key {
+        if *key == KeyCode::Escape {
+            capture.0 = None;
+            return;
+        }
+        Bind::Key(*key)
+    } else if let Some(button) = buttons.get_just_pressed().next() {
+        Bind::Mouse(*button)
+    } else if let Some(button) = gamepad_buttons.get_just_pressed().next() {
+        Bind::GamepadButton(button.button_type)
+    } else if let Some((axis, value)) = moved_axis(&gamepads, &gamepad_axes) {
+        Bind::GamepadAxis {
+            axis,
+            threshold: AXIS_THRESHOLD,
+            direction: if value > 0. {
+                AxisDirection::Positive
+            } else {
+                AxisDirection::Negative
+            },
+        }
+    } else {
+        return;
+    };
+    // Modifiers only make sense with keyboard and mouse binds
+    if !matches!(bind, Bind::Key(_) | Bind::Mouse(_)) {
+        modifiers.clear();
+    }
+    let chord = Chord { modifiers, bind };
+    match rebind.mode {
+        RebindMode::Set => binds.set(rebind.action, chord),
+        RebindMode::Add => binds.add(rebind.action, chord),
+        RebindMode::Remove => {
+            // Axis binds are matched by direction, their dead zone may differ from the captured one
+            let bound = binds
+                .get(rebind.action)
+                .iter()
+                .find(|bound| is_same_input(bound, &chord))
+                .cloned();
+            match bound {
+                Some(bound) => binds.remove(rebind.action, &bound),
+                None => info!("{chord} is not bound to {}", rebind.action.name()),
+            }
+        }
+    }
+    capture.0 = None;
+}
+
+fn is_same_input(a: &Chord, b: &Chord) -> bool {
+    a.modifiers == b.modifiers
+        && match (a.bind, b.bind) {
+            (
+                Bind::GamepadAxis {
+                    axis, direction, ..
+                },
+                Bind::GamepadAxis {
+                    axis: other_axis,
+                    direction: other_direction,
+                    ..
+                },
+            ) => axis == other_axis && direction == other_direction,
+            (bind, other) => bind == other,
+        }
+}
+
+// Axis of any connected gamepad which is moved far enough to be captured
+fn moved_axis(gamepads: &Gamepads, axes: &Axis<GamepadAxis>) -> Option<(GamepadAxisType, f32)> {
+    const AXES: [GamepadAxisType; 6] = [
+        GamepadAxisType::LeftStickX,
+        GamepadAxisType::LeftStickY,
+        GamepadAxisType::LeftZ,
+        GamepadAxisType::RightStickX,
+        GamepadAxisType::RightStickY,
+        GamepadAxisType::RightZ,
+    ];
+    gamepads.iter().find_map(|gamepad| {
+        AXES.iter().find_map(|axis| {
+            let value = axes.get(GamepadAxis::new(gamepad, *axis))?;
+            (value.abs() >= AXIS_CAPTURE).then_some((*axis, value))
+        })
+    })
+}
+
+fn click_buttons(
+    mut buttons: Query<(&Interaction, &SettingsButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut capture: ResMut<Capture>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+    back: Res<SettingsReturn>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, button, mut color) in buttons.iter_mut() {
+        *color = match interaction {
+            Interaction::None => BUTTON_COLOR,
+            _ => FOCUSED_COLOR,
+        }
+        .into();
+        if *interaction != Interaction::Pressed || capture.0.is_some() {
+            continue;
+        }
+        match button {
+            SettingsButton::Bind(action) => {
+                capture.0 = Some(Rebind {
+                    action: *action,
+                    mode: RebindMode::Set,
+                })
+            }
+            SettingsButton::AddBind(action) => {
+                capture.0 = Some(Rebind {
+                    action: *action,
+                    mode: RebindMode::Add,
+                })
+            }
+            SettingsButton::RemoveBind(action) => {
+                capture.0 = Some(Rebind {
+                    action: *action,
+                    mode: RebindMode::Remove,
+                })
+            }
+            SettingsButton::Fullscreen => {
+                if let Ok(mut window) = window.get_single_mut() {
+                    window.mode = match window.mode {
+                        WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+                        _ => WindowMode::Windowed,
+                    };
+                }
+            }
+            SettingsButton::Back => next_state.set(back.0.clone()),
+        }
+    }
+}
+
+fn drag_sliders(
+    sliders: Query<(&Interaction, &SliderSetting, &Node, &GlobalTransform)>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    mut scale: ResMut<ScaleSize>,
+    mut volume: ResMut<Volume>,
+) {
+    let Some(cursor) = window.get_single().ok().and_then(|w| w.cursor_position()) else {
+        return;
+    };
+    for (interaction, setting, node, transform) in sliders.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let width = node.size().x;
+        let left = transform.translation().x - width / 2.;
+        let fraction = ((cursor.x - left) / width).clamp(0., 1.);
+        let (min, max) = setting.range();
+        let value = min + (max - min) * fraction;
+        match setting {
+            SliderSetting::ZoomSensitivity => scale.0 = value,
+            SliderSetting::MasterVolume => volume.master = value,
+            SliderSetting::MusicVolume => volume.music = value,
+            SliderSetting::SfxVolume => volume.sfx = value,
+        }
+    }
+}
+
+fn scroll_binds(
+    mut wheel: EventReader<MouseWheel>,
+    mut lists: Query<(&mut BindList, &mut Style, &Node, &Parent)>,
+    panels: Query<&Node>,
+) {
+    for event in wheel.read() {
+        let delta = match event.unit {
+            MouseScrollUnit::Line => event.y * SCROLL_LINE,
+            MouseScrollUnit::Pixel => event.y,
+        };
+        for (mut list, mut style, node, parent) in lists.iter_mut() {
+            let Ok(panel) = panels.get(parent.get()) else {
+                continue;
+            };
+            let hidden = (node.size().y - panel.size().y).max(0.);
+            list.offset = (list.offset + delta).clamp(-hidden, 0.);
+            style.top = Val::Px(list.offset);
+        }
+    }
+}
+
+// Conflicts are found for every pair of actions, so texts are only rebuilt when something changes
+fn update_binds(
+    buttons: Query<(&SettingsButton, &Children)>,
+    spawned: Query<(), Added<SettingsButton>>,
+    mut texts: Query<&mut Text>,
+    conflict_text: Query<Entity, With<ConflictText>>,
+    binds: Res<ControlBinds>,
+    capture: Res<Capture>,
+) {
+    if !binds.is_changed() && !capture.is_changed() && spawned.is_empty() {
+        return;
+    }
+    let mut conflicts = Vec::new();
+    for (button, children) in buttons.iter() {
+        let SettingsButton::Bind(action) = button else {
+            continue;
+        };
+        let Ok(mut text) = texts.get_mut(children[0]) else {
+            continue;
+        };
+        let others: Vec<_> = binds
+            .conflicts(*action)
+            .iter()
+            .map(|other| other.name())
+            .collect();
+        let section = &mut text.sections[0];
+        if let Some(rebind) = capture.0.as_ref().filter(|rebind| rebind.action == *action) {
+            section.value = String::from(match rebind.mode {
+                RebindMode::Remove => "Press the bind to remove...",
+                _ => "Press a button...",
+            });
+            section.style.color = CAPTURE_COLOR;
+        } else {
+            let chords = binds.get(*action);
+            section.value = if chords.is_empty() {
+                Bind::None.to_string()
+            } else {
+                chords
+                    .iter()
+                    .map(|chord| chord.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            section.style.color = if others.is_empty() {
+                TEXT_COLOR
+            } else {
+                CONFLICT_COLOR
+            };
+        }
+        if !others.is_empty() {
+            conflicts.push(format!(
+                "{} shares its bind with {}",
+                action.name(),
+                others.join(", ")
+            ));
+        }
+    }
+    conflicts.sort();
+    if let Ok(entity) = conflict_text.get_single() {
+        if let Ok(mut text) = texts.get_mut(entity) {
+            text.sections[0].value = conflicts.join("\n");
+            text.sections[0].style.color = CONFLICT_COLOR;
+        }
+    }
+}
+
+fn update_sliders(
+    mut fills: Query<(&SliderFill, &mut Style)>,
+    mut values: Query<(&SliderValue, &mut Text)>,
+    scale: Res<ScaleSize>,
+    volume: Res<Volume>,
+) {
+    let value = |setting: &SliderSetting| match setting {
+        SliderSetting::ZoomSensitivity => scale.0,
+        SliderSetting::MasterVolume => volume.master,
+        SliderSetting::MusicVolume => volume.music,
+        SliderSetting::SfxVolume => volume.sfx,
+    };
+    for (fill, mut style) in fills.iter_mut() {
+        let (min, max) = fill.0.range();
+        let fraction = ((value(&fill.0) - min) / (max - min)).clamp(0., 1.);
+        style.width = Val::Percent(fraction * 100.);
+    }
+    for (label, mut text) in values.iter_mut() {
+        text.sections[0].value = label.0.format(value(&label.0));
+    }
+}
+
+fn update_labels(
+    mut labels: Query<&mut Text, With<FullscreenLabel>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let fullscreen = window
+        .get_single()
+        .map(|window| window.mode != WindowMode::Windowed)
+        .unwrap_or(false);
+    for mut text in labels.iter_mut() {
+        text.sections[0].value = String::from(if fullscreen { "On" } else { "Off" });
+    }
+}
@@ -0,0 +1,185 @@
+use bevy::{prelude::*, transform::TransformSystem};
+
+use crate::{settings::controls::*, GameState, Tick};
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_actions::<SpeedAction>()
+            .init_resource::<TickRate>()
+            .init_resource::<GameSpeed>()
+            .configure_sets(FixedUpdate, Simulation.run_if(in_state(GameState::Playing)))
+            .add_systems(
+                FixedUpdate,
+                (
+                    store_previous.before(Simulation),
+                    update_tick
+                        .after(Simulation)
+                        .run_if(in_state(GameState::Playing)),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    reset_speed.in_set(crate::GameSetup),
+                    change_speed.run_if(in_state(GameState::Playing)),
+                    apply_tick_rate.run_if(resource_changed::<TickRate>()),
+                    apply_game_speed.run_if(resource_changed::<GameSpeed>()),
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                interpolate.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Systems that advance the world, they run in `FixedUpdate` once per [`Tick`]
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Simulation;
+
+/// Simulation ticks per second of game time
+#[derive(Resource)]
+pub struct TickRate(pub f64);
+impl Default for TickRate {
+    fn default() -> Self {
+        Self(60.)
+    }
+}
+impl TickRate {
+    /// Ticks which pass in `seconds` of game time, at least one
+    pub fn ticks(&self, seconds: f32) -> u32 {
+        ((seconds as f64 * self.0).round() as u32).max(1)
+    }
+}
+
+/// How fast game time runs compared to real time
+#[derive(Resource, Default, Clone, Copy, PartialEq, Debug)]
+pub enum GameSpeed {
+    Paused,
+    #[default]
+    Normal,
+    Double,
+    Quadruple,
+}
+impl GameSpeed {
+    const ALL: [GameSpeed; 4] = [
+        GameSpeed::Paused,
+        GameSpeed::Normal,
+        GameSpeed::Double,
+        GameSpeed::Quadruple,
+    ];
+    pub fn multiplier(&self) -> f32 {
+        match self {
+            GameSpeed::Paused => 0.,
+            GameSpeed::Normal => 1.,
+            GameSpeed::Double => 2.,
+            GameSpeed::Quadruple => 4.,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum SpeedAction {
+    Slower,
+    Faster,
+}
+impl Action for SpeedAction {
+    const ALL: &'static [Self] = &[SpeedAction::Slower, SpeedAction::Faster];
+    fn name(&self) -> &'static str {
+        match self {
+            SpeedAction::Slower => "speed_down",
+            SpeedAction::Faster => "speed_up",
+        }
+    }
+    fn default_binds(&self) -> Vec<Chord> {
+        let key = match self {
+            SpeedAction::Slower => KeyCode::Minus,
+            SpeedAction::Faster => KeyCode::Equals,
+        };
+        vec![Bind::Key(key).into()]
+    }
+    // Like pause it controls time, so it is not recorded and works while replaying
+    fn context(&self) -> InputContext {
+        InputContext::Global
+    }
+}
+
+/// Position of an entity in the simulation, `Transform` shows it between two ticks
+#[derive(Component, Default)]
+pub struct Interpolated {
+    previous: Vec2,
+    pub current: Vec2,
+}
+impl Interpolated {
+    pub fn new(position: Vec2) -> Self {
+        Self {
+            previous: position,
+            current: position,
+        }
+    }
+    /// Moves without sliding from the old position
+    pub fn teleport(&mut self, position: Vec2) {
+        *self = Self::new(position);
+    }
+}
+
+// logic
+fn update_tick(mut tick: ResMut<Tick>) {
+    tick.0 += 1;
+}
+
+fn store_previous(mut query: Query<&mut Interpolated>) {
+    for mut interpolated in query.iter_mut() {
+        interpolated.previous = interpolated.current;
+    }
+}
+
+pub(crate) fn interpolate(
+    time: Res<Time<Fixed>>,
+    mut query: Query<(&Interpolated, &mut Transform)>,
+) {
+    // Part of the next tick which has already passed
+    let t = time.overstep_percentage();
+    for (interpolated, mut trans) in query.iter_mut() {
+        let position = interpolated.previous.lerp(interpolated.current, t);
+        trans.translation.x = position.x;
+        trans.translation.y = position.y;
+    }
+}
+
+fn reset_speed(mut speed: ResMut<GameSpeed>) {
+    *speed = GameSpeed::Normal;
+}
+
+fn change_speed(controls: Res<ControlBinds>, mut speed: ResMut<GameSpeed>) {
+    let index = GameSpeed::ALL
+        .iter()
+        .position(|s| s == &*speed)
+        .unwrap_or(1);
+    let index = if controls.is_just_pressed(SpeedAction::Slower) {
+        index.saturating_sub(1)
+    } else if controls.is_just_pressed(SpeedAction::Faster) {
+        (index + 1).min(GameSpeed::ALL.len() - 1)
+    } else {
+        return;
+    };
+    if GameSpeed::ALL[index] != *speed {
+        *speed = GameSpeed::ALL[index];
+        info!("Game speed: {:?}", *speed);
+    }
+}
+
+fn apply_tick_rate(rate: Res<TickRate>, mut time: ResMut<Time<Fixed>>) {
+    time.set_timestep_hz(rate.0);
+}
+
+fn apply_game_speed(speed: Res<GameSpeed>, mut time: ResMut<Time<Virtual>>) {
+    if *speed == GameSpeed::Paused {
+        time.pause();
+    } else {
+        time.unpause();
+        time.set_relative_speed(speed.multiplier());
+    }
+}
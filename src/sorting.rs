@@ -0,0 +1,100 @@
+use bevy::{prelude::*, transform::TransformSystem};
+
+use crate::simulation::interpolate;
+
+/// Map tiles, the layers of a cell are stacked just above it
+pub const MAP_LAYER: f32 = 100.;
+/// Buildings, items and the player, sorted by y so they overlap like standing objects
+pub const OBJECT_LAYER: f32 = 200.;
+
+// Z room of one layer, y sorting stays inside it
+const LAYER_DEPTH: f32 = 100.;
+// World height sorted apart, the whole map fits into it
+const SORT_HEIGHT: f32 = 8192.;
+
+pub struct SortingPlugin;
+
+impl Plugin for SortingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            sort_by_y
+                .after(interpolate)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Sets the z of an entity without a parent from its layer, and its y if `y_sort` is set
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct SortLayer {
+    /// One of the `*_LAYER` constants
+    pub layer: f32,
+    pub y_sort: bool,
+}
+impl SortLayer {
+    /// Lower entities of the layer are drawn in front of higher ones
+    pub fn sorted(layer: f32) -> Self {
+        Self {
+            layer,
+            y_sort: true,
+        }
+    }
+    /// Every entity of the layer is at the same depth
+    pub fn flat(layer: f32) -> Self {
+        Self {
+            layer,
+            y_sort: false,
+        }
+    }
+    pub fn z(&self, y: f32) -> f32 {
+        if !self.y_sort {
+            return self.layer;
+        }
+        // The top of the sorted height is at the bottom of the layer
+        let depth = (0.5 - y / SORT_HEIGHT).clamp(0., 1.);
+        self.layer + depth * (LAYER_DEPTH - 1.)
+    }
+}
+
+// logic
+fn sort_by_y(mut query: Query<(&SortLayer, &mut Transform), Without<Parent>>) {
+    for (layer, mut trans) in query.iter_mut() {
+        let z = layer.z(trans.translation.y);
+        // Only touch the transform when the depth changes
+        if trans.translation.z != z {
+            trans.translation.z = z;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lower_is_in_front() {
+        let layer = SortLayer::sorted(OBJECT_LAYER);
+        assert!(layer.z(-10.) > layer.z(0.));
+        assert!(layer.z(0.) > layer.z(10.));
+        assert!(layer.z(-1.) > layer.z(0.));
+    }
+
+    #[test]
+    fn layers_do_not_overlap() {
+        let map = SortLayer::sorted(MAP_LAYER);
+        let objects = SortLayer::sorted(OBJECT_LAYER);
+        for y in [-1e6, -SORT_HEIGHT / 2., 0., SORT_HEIGHT / 2., 1e6] {
+            assert!(objects.z(y) >= OBJECT_LAYER);
+            assert!(objects.z(y) < OBJECT_LAYER + LAYER_DEPTH);
+            assert!(map.z(y) < objects.z(1e6));
+        }
+    }
+
+    #[test]
+    fn flat_layers_ignore_y() {
+        let layer = SortLayer::flat(MAP_LAYER);
+        assert_eq!(layer.z(-500.), MAP_LAYER);
+        assert_eq!(layer.z(500.), MAP_LAYER);
+    }
+}
@@ -0,0 +1,277 @@
+use bevy::{
+    input::{touch::TouchInput, InputSystem},
+    prelude::*,
+};
+
+use crate::{
+    player::PlayerCamera,
+    settings::{controls::*, ScaleSize},
+    GameState, FONT,
+};
+
+const STICK_SIZE: f32 = 160.;
+const KNOB_SIZE: f32 = 64.;
+const BUTTON_SIZE: f32 = 72.;
+const MARGIN: f32 = 32.;
+const OVERLAY_COLOR: Color = Color::rgba(1., 1., 1., 0.15);
+const KNOB_COLOR: Color = Color::rgba(1., 1., 1., 0.4);
+// Phones show touch controls from the start
+const TOUCH_DEVICE: bool = cfg!(any(target_os = "android", target_os = "ios"));
+// Zoom change per pixel of pinch, multiplied by the zoom sensitivity
+const PINCH_SPEED: f32 = 0.05;
+
+pub struct TouchPlugin;
+
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchControls>()
+            .add_systems(Startup, setup_overlay)
+            .add_systems(
+                PreUpdate,
+                (detect_touch, update_stick, update_buttons)
+                    .chain()
+                    .after(InputSystem)
+                    .before(CheckControls),
+            )
+            .add_systems(
+                Update,
+                (
+                    show_overlay,
+                    pinch_zoom.run_if(in_state(GameState::Playing)),
+                ),
+            );
+    }
+}
+
+/// On-screen controls are shown while playing once the device is touched
+#[derive(Resource)]
+struct TouchControls {
+    enabled: bool,
+}
+impl Default for TouchControls {
+    fn default() -> Self {
+        Self {
+            enabled: TOUCH_DEVICE,
+        }
+    }
+}
+
+#[derive(Component)]
+struct TouchOverlay;
+
+/// Virtual joystick, it follows the touch which started on it
+#[derive(Component, Default)]
+struct TouchStick {
+    touch: Option<u64>,
+}
+
+#[derive(Component)]
+struct StickKnob;
+
+#[derive(Component)]
+struct OverlayButton(TouchButton);
+
+// logic
+fn setup_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load(FONT);
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    ..Default::default()
+                },
+                visibility: Visibility::Hidden,
+                z_index: ZIndex::Global(5),
+                ..Default::default()
+            },
+            Name::new("Touch controls"),
+            TouchOverlay,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            left: Val::Px(MARGIN),
+                            bottom: Val::Px(MARGIN),
+                            width: Val::Px(STICK_SIZE),
+                            height: Val::Px(STICK_SIZE),
+                            ..Default::default()
+                        },
+                        background_color: OVERLAY_COLOR.into(),
+                        ..Default::default()
+                    },
+                    TouchStick::default(),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: knob_style(Vec2::ZERO),
+                            background_color: KNOB_COLOR.into(),
+                            ..Default::default()
+                        },
+                        StickKnob,
+                    ));
+                });
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            right: Val::Px(MARGIN),
+                            top: Val::Px(MARGIN),
+                            width: Val::Px(BUTTON_SIZE),
+                            height: Val::Px(BUTTON_SIZE),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        background_color: OVERLAY_COLOR.into(),
+                        ..Default::default()
+                    },
+                    OverlayButton(TouchButton::Pause),
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "II",
+                        TextStyle {
+                            font,
+                            font_size: 32.,
+                            color: Color::WHITE,
+                        },
+                    ));
+                });
+        });
+}
+
+// Knob position for a stick deflection, y goes down like on the screen
+fn knob_style(offset: Vec2) -> Style {
+    let center = (STICK_SIZE - KNOB_SIZE) / 2.;
+    Style {
+        position_type: PositionType::Absolute,
+        left: Val::Px(center + offset.x * STICK_SIZE / 2.),
+        top: Val::Px(center + offset.y * STICK_SIZE / 2.),
+        width: Val::Px(KNOB_SIZE),
+        height: Val::Px(KNOB_SIZE),
+        ..Default::default()
+    }
+}
+
+fn overlay_active(touch: &TouchControls, state: &State<GameState>) -> bool {
+    touch.enabled && *state.get() == GameState::Playing
+}
+
+fn detect_touch(mut events: EventReader<TouchInput>, mut touch: ResMut<TouchControls>) {
+    if events.read().next().is_some() && !touch.enabled {
+        info!("Touch input detected, showing touch controls");
+        touch.enabled = true;
+    }
+}
+
+fn update_stick(
+    touches: Res<Touches>,
+    touch: Res<TouchControls>,
+    state: Res<State<GameState>>,
+    mut input: ResMut<VirtualInput>,
+    mut stick: Query<(&mut TouchStick, &Node, &GlobalTransform)>,
+    mut knob: Query<&mut Style, With<StickKnob>>,
+) {
+    let Ok((mut stick, node, transform)) = stick.get_single_mut() else {
+        return;
+    };
+    let rect = node.logical_rect(transform);
+    if !overlay_active(&touch, &state) {
+        stick.touch = None;
+    } else {
+        // Claim a touch which starts on the stick, release it when the finger is lifted
+        if stick
+            .touch
+            .is_some_and(|id| touches.get_pressed(id).is_none())
+        {
+            stick.touch = None;
+        }
+        if stick.touch.is_none() {
+            stick.touch = touches
+                .iter_just_pressed()
+                .find(|finger| rect.contains(finger.position()))
+                .map(|finger| finger.id());
+        }
+    }
+    let offset = stick
+        .touch
+        .and_then(|id| touches.get_pressed(id))
+        .map_or(Vec2::ZERO, |finger| {
+            ((finger.position() - rect.center()) / (STICK_SIZE / 2.)).clamp_length_max(1.)
+        });
+    input.stick = Vec2::new(offset.x, -offset.y);
+    for mut style in knob.iter_mut() {
+        *style = knob_style(offset);
+    }
+}
+
+fn update_buttons(
+    touches: Res<Touches>,
+    touch: Res<TouchControls>,
+    state: Res<State<GameState>>,
+    mut input: ResMut<VirtualInput>,
+    buttons: Query<(&OverlayButton, &Node, &GlobalTransform)>,
+) {
+    let active = overlay_active(&touch, &state);
+    input.buttons.clear();
+    for (button, node, transform) in buttons.iter() {
+        let rect = node.logical_rect(transform);
+        if active
+            && touches
+                .iter()
+                .any(|finger| rect.contains(finger.position()))
+        {
+            input.buttons.press(button.0);
+        } else {
+            input.buttons.release(button.0);
+        }
+    }
+}
+
+fn show_overlay(
+    touch: Res<TouchControls>,
+    state: Res<State<GameState>>,
+    mut overlay: Query<&mut Visibility, With<TouchOverlay>>,
+) {
+    let visibility = if overlay_active(&touch, &state) {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    for mut overlay in overlay.iter_mut() {
+        overlay.set_if_neq(visibility);
+    }
+}
+
+fn pinch_zoom(
+    touches: Res<Touches>,
+    stick: Query<&TouchStick>,
+    mut cam: Query<&mut PlayerCamera>,
+    scale: Res<ScaleSize>,
+    mut last_distance: Local<Option<f32>>,
+) {
+    // Fingers on the joystick don't zoom
+    let stick_touch = stick.get_single().ok().and_then(|stick| stick.touch);
+    let fingers: Vec<_> = touches
+        .iter()
+        .filter(|finger| Some(finger.id()) != stick_touch)
+        .collect();
+    let [a, b] = fingers[..] else {
+        *last_distance = None;
+        return;
+    };
+    let distance = a.position().distance(b.position());
+    if let (Some(last), Ok(mut cam)) = (*last_distance, cam.get_single_mut()) {
+        // Spreading the fingers zooms in
+        cam.zoom((last - distance) * scale.0 * PINCH_SPEED);
+    }
+    *last_distance = Some(distance);
+}
@@ -0,0 +1,196 @@
+// Every test crate uses its own part of the harness
+#![allow(dead_code)]
+
+use bevy::{
+    asset::LoadState, prelude::*, sprite::TextureAtlasBuilderError, time::TimeUpdateStrategy,
+    utils::HashMap,
+};
+use miner::{
+    resources::{TextureSetAsset, TextureSetLoader},
+    settings::controls::{ActionState, ControlBinds},
+    simulation::Simulation,
+    GamePlugin, GameState, NewGame, Tick,
+};
+
+// Frames a step may take before the test fails
+const MAX_FRAMES: u32 = 1000;
+
+/// Headless game which is advanced by hand, one simulation tick at a time
+pub struct TestGame {
+    pub app: App,
+}
+impl TestGame {
+    /// Builds the game and waits for the main menu, assets are read from `tests/fixtures`
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin {
+                file_path: "tests/fixtures".to_string(),
+                ..Default::default()
+            },
+            GamePlugin::headless(),
+        ))
+        .init_asset::<Image>()
+        .init_asset::<TextureAtlas>()
+        .init_asset::<TextureSetAsset>()
+        .init_asset_loader::<TextureSetLoader>()
+        .init_resource::<HeldActions>()
+        .add_systems(FixedUpdate, hold_actions.before(Simulation));
+
+        // Every frame is exactly one tick long
+        let timestep = app.world.resource::<Time<Fixed>>().timestep();
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep));
+
+        let mut game = Self { app };
+        game.run_until("main menu", |app| {
+            *app.world.resource::<State<GameState>>() == GameState::Menu
+        });
+        game
+    }
+
+    /// Starts a new world, the first tick runs on the next [`TestGame::tick`]
+    pub fn start(&mut self, seed: u64) -> &mut Self {
+        self.app.world.send_event(NewGame { seed });
+        self.app
+            .world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        self.app.update();
+        self
+    }
+
+    /// Runs `ticks` simulation ticks
+    pub fn tick(&mut self, ticks: u64) -> &mut Self {
+        let target = self.ticks() + ticks;
+        self.run_until("ticks", |app| app.world.resource::<Tick>().0 >= target);
+        self
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.app.world.resource::<Tick>().0
+    }
+
+    /// Keeps the action pressed on every tick until it is released
+    pub fn hold(&mut self, action: &str) -> &mut Self {
+        self.hold_value(action, 1.)
+    }
+
+    /// Holds the action partially, like a stick which is not fully tilted
+    pub fn hold_value(&mut self, action: &str, value: f32) -> &mut Self {
+        let binds = self.app.world.resource::<ControlBinds>();
+        assert!(
+            binds.find(action).is_some(),
+            "Action {action} is not registered"
+        );
+        self.app
+            .world
+            .resource_mut::<HeldActions>()
+            .0
+            .insert(action.to_string(), value);
+        self
+    }
+
+    pub fn release(&mut self, action: &str) -> &mut Self {
+        self.app
+            .world
+            .resource_mut::<HeldActions>()
+            .0
+            .remove(action);
+        self
+    }
+
+    /// Loads a texture set from the fixtures and builds its atlas
+    pub fn load_tset(&mut self, path: &str) -> Handle<TextureSetAsset> {
+        let (handle, state) = self.try_load_tset(path);
+        assert_eq!(state, LoadState::Loaded, "{path} failed to load");
+        if let Err(e) = self.build_tset(&handle) {
+            panic!("atlas of {path} is not built: {e}");
+        }
+        handle
+    }
+
+    /// Builds the atlas of a loaded texture set
+    pub fn build_tset(
+        &mut self,
+        handle: &Handle<TextureSetAsset>,
+    ) -> Result<(), TextureAtlasBuilderError> {
+        self.app
+            .world
+            .resource_scope(|world, mut tsets: Mut<Assets<TextureSetAsset>>| {
+                world.resource_scope(|world, mut images: Mut<Assets<Image>>| {
+                    let mut atlases = world.resource_mut::<Assets<TextureAtlas>>();
+                    tsets
+                        .get_mut(handle)
+                        .unwrap()
+                        .check_or_build(&mut images, &mut atlases)
+                })
+            })
+    }
+
+    /// Waits until the texture set is loaded or failed, the atlas is not built
+    pub fn try_load_tset(&mut self, path: &str) -> (Handle<TextureSetAsset>, LoadState) {
+        let handle: Handle<TextureSetAsset> = self
+            .app
+            .world
+            .resource::<AssetServer>()
+            .load(path.to_string());
+        let id = handle.id();
+        let state = |app: &App| app.world.resource::<AssetServer>().get_load_state(id);
+        self.run_until(path, |app| {
+            matches!(state(app), Some(LoadState::Loaded | LoadState::Failed))
+        });
+        let state = state(&self.app).unwrap();
+        (handle, state)
+    }
+
+    pub fn tset(&self, handle: &Handle<TextureSetAsset>) -> &TextureSetAsset {
+        self.app
+            .world
+            .resource::<Assets<TextureSetAsset>>()
+            .get(handle)
+            .unwrap()
+    }
+
+    pub fn atlas(&self, handle: &Handle<TextureAtlas>) -> &TextureAtlas {
+        self.app
+            .world
+            .resource::<Assets<TextureAtlas>>()
+            .get(handle)
+            .unwrap()
+    }
+
+    /// The only entity with `F`, fails if there is none or many
+    pub fn single<C: Component, F: Component>(&mut self) -> &C {
+        self.app
+            .world
+            .query_filtered::<&C, With<F>>()
+            .single(&self.app.world)
+    }
+
+    fn run_until(&mut self, what: &str, done: impl Fn(&mut App) -> bool) {
+        for _ in 0..MAX_FRAMES {
+            if done(&mut self.app) {
+                return;
+            }
+            self.app.update();
+        }
+        panic!("Waiting for {what} took more than {MAX_FRAMES} frames");
+    }
+}
+
+/// Actions pressed by the test with their values
+#[derive(Resource, Default)]
+struct HeldActions(HashMap<String, f32>);
+
+// Runs after the real input is read, so only the test controls the game
+fn hold_actions(held: Res<HeldActions>, mut binds: ResMut<ControlBinds>) {
+    for (name, value) in held.0.iter() {
+        let Some(action) = binds.find(name) else {
+            continue;
+        };
+        let mut state = ActionState::default();
+        state.pressed = true;
+        binds.set_state(action, state, *value);
+    }
+}
@@ -0,0 +1,176 @@
+mod common;
+
+use bevy::prelude::*;
+use common::TestGame;
+use miner::{
+    map::{MapLayer, MapTiles, SetTile, Tile},
+    player::Player,
+    resources::TSetTile,
+    sorting::{SortLayer, OBJECT_LAYER},
+};
+
+fn tiles(game: &TestGame) -> &MapTiles {
+    game.app.world.resource::<MapTiles>()
+}
+
+fn set(game: &mut TestGame, cell: IVec2, layer: MapLayer, tile: Option<&str>) {
+    game.app.world.send_event(SetTile {
+        cell,
+        layer,
+        tile: tile.map(|name| (name.to_string(), TSetTile::Single)),
+    });
+    game.tick(1);
+}
+
+#[test]
+fn every_cell_has_ground() {
+    let mut game = TestGame::new();
+    game.start(1).tick(1);
+    for cell in [IVec2::ZERO, IVec2::new(99, 0), IVec2::new(42, 99)] {
+        let ground = tiles(&game).get(cell, MapLayer::Ground).unwrap();
+        let tile = game.app.world.get::<Tile>(ground).unwrap();
+        assert_eq!(tile.cell, cell);
+        assert_eq!(tile.layer, MapLayer::Ground);
+    }
+    assert!(tiles(&game)
+        .get(IVec2::new(100, 0), MapLayer::Ground)
+        .is_none());
+    assert!(tiles(&game)
+        .get(IVec2::new(-1, 5), MapLayer::Ground)
+        .is_none());
+}
+
+#[test]
+fn same_seed_same_buildings() {
+    let buildings = |seed| {
+        let mut game = TestGame::new();
+        game.start(seed).tick(1);
+        let mut cells: Vec<_> = game
+            .app
+            .world
+            .query::<&Tile>()
+            .iter(&game.app.world)
+            .filter(|tile| tile.layer == MapLayer::Building)
+            .map(|tile| (tile.cell.x, tile.cell.y))
+            .collect();
+        cells.sort();
+        cells
+    };
+    let first = buildings(7);
+    assert!(!first.is_empty());
+    assert_eq!(first, buildings(7));
+    assert_ne!(first, buildings(8));
+}
+
+#[test]
+fn ore_and_trees_are_generated() {
+    let mut game = TestGame::new();
+    game.start(1).tick(1);
+    let layers: Vec<_> = game
+        .app
+        .world
+        .query::<&Tile>()
+        .iter(&game.app.world)
+        .map(|tile| (tile.cell, tile.layer))
+        .collect();
+    let count = |layer| layers.iter().filter(|(_, l)| *l == layer).count();
+    assert!(count(MapLayer::Ore) > 0);
+    assert!(count(MapLayer::Decoration) > 0);
+    // Trees don't grow on buildings
+    for (cell, _) in layers.iter().filter(|(_, l)| *l == MapLayer::Decoration) {
+        assert!(tiles(&game).get(*cell, MapLayer::Building).is_none());
+    }
+}
+
+#[test]
+fn layers_stack_in_a_cell() {
+    let mut game = TestGame::new();
+    game.start(1).tick(1);
+    let cell = IVec2::new(3, 4);
+    // Buildings are sorted apart from the map
+    set(&mut game, cell, MapLayer::Building, None);
+    set(&mut game, cell, MapLayer::Decoration, Some("grass"));
+    set(&mut game, cell, MapLayer::Ore, Some("water"));
+
+    let layers: Vec<_> = tiles(&game).layers(cell).collect();
+    let names: Vec<_> = layers.iter().map(|(layer, _)| *layer).collect();
+    assert_eq!(names[0], MapLayer::Ground);
+    assert_eq!(
+        names[names.len() - 2..],
+        [MapLayer::Ore, MapLayer::Decoration]
+    );
+    // Higher layers are drawn above lower ones at the same place
+    let transforms: Vec<_> = layers
+        .iter()
+        .map(|(_, entity)| *game.app.world.get::<Transform>(*entity).unwrap())
+        .collect();
+    for pair in transforms.windows(2) {
+        assert!(pair[0].translation.z < pair[1].translation.z);
+        assert_eq!(
+            pair[0].translation.truncate(),
+            pair[1].translation.truncate()
+        );
+    }
+}
+
+#[test]
+fn tiles_are_replaced_and_cleared() {
+    let mut game = TestGame::new();
+    game.start(1).tick(1);
+    let cell = IVec2::new(10, 10);
+    set(&mut game, cell, MapLayer::Ore, Some("water"));
+    let ore = tiles(&game).get(cell, MapLayer::Ore).unwrap();
+    // Replacing keeps the entity
+    set(&mut game, cell, MapLayer::Ore, Some("grass"));
+    assert_eq!(tiles(&game).get(cell, MapLayer::Ore), Some(ore));
+
+    set(&mut game, cell, MapLayer::Ore, None);
+    assert!(tiles(&game).get(cell, MapLayer::Ore).is_none());
+    assert!(game.app.world.get_entity(ore).is_none());
+    assert!(tiles(&game).get(cell, MapLayer::Ground).is_some());
+}
+
+#[test]
+fn new_game_rebuilds_the_map() {
+    let mut game = TestGame::new();
+    game.start(1).tick(1);
+    let cell = IVec2::new(1, 1);
+    set(&mut game, cell, MapLayer::Ore, Some("water"));
+    let ore = tiles(&game).get(cell, MapLayer::Ore).unwrap();
+    let ground = tiles(&game).get(cell, MapLayer::Ground).unwrap();
+
+    game.start(1).tick(1);
+    assert!(game.app.world.get_entity(ore).is_none());
+    assert!(game.app.world.get_entity(ground).is_none());
+    assert!(tiles(&game).get(cell, MapLayer::Ground).is_some());
+}
+
+#[test]
+fn buildings_are_sorted_with_the_player() {
+    let mut game = TestGame::new();
+    game.start(1).tick(1);
+    // The player stands at the center of the map, between these two cells
+    let behind = IVec2::new(50, 52);
+    let in_front = IVec2::new(50, 48);
+    set(&mut game, behind, MapLayer::Building, Some("converyor"));
+    set(&mut game, in_front, MapLayer::Building, Some("converyor"));
+    let z = |game: &TestGame, entity| {
+        game.app
+            .world
+            .get::<Transform>(entity)
+            .unwrap()
+            .translation
+            .z
+    };
+
+    let behind = tiles(&game).get(behind, MapLayer::Building).unwrap();
+    let in_front = tiles(&game).get(in_front, MapLayer::Building).unwrap();
+    assert!(game.app.world.get::<Parent>(behind).is_none());
+    assert_eq!(
+        game.app.world.get::<SortLayer>(behind),
+        Some(&SortLayer::sorted(OBJECT_LAYER))
+    );
+    let player = game.single::<Transform, Player>().translation.z;
+    assert!(z(&game, behind) < player);
+    assert!(z(&game, in_front) > player);
+}
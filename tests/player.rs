@@ -0,0 +1,131 @@
+mod common;
+
+use bevy::prelude::*;
+use common::TestGame;
+use miner::{
+    health::{DamageEvent, Dead, Health},
+    player::Player,
+    resources::Facing,
+    simulation::Interpolated,
+    sorting::SortLayer,
+};
+
+// Pixels per second of the player
+const SPEED: f32 = 300.;
+// Ticks per second of the default tick rate
+const TICK_RATE: u64 = 60;
+
+fn position(game: &mut TestGame) -> Vec2 {
+    game.single::<Interpolated, Player>().current
+}
+
+#[test]
+fn ticks_count_from_new_game() {
+    let mut game = TestGame::new();
+    game.start(1).tick(10);
+    assert_eq!(game.ticks(), 10);
+    game.start(2);
+    assert_eq!(game.ticks(), 0);
+}
+
+#[test]
+fn stands_still_without_input() {
+    let mut game = TestGame::new();
+    game.start(1);
+    let start = position(&mut game);
+    game.tick(30);
+    assert_eq!(position(&mut game), start);
+}
+
+#[test]
+fn moves_with_speed_per_second() {
+    let mut game = TestGame::new();
+    game.start(1);
+    let start = position(&mut game);
+    game.hold("move_right").tick(TICK_RATE);
+    let moved = position(&mut game) - start;
+    assert!((moved.x - SPEED).abs() < 0.01, "moved {moved}");
+    assert_eq!(moved.y, 0.);
+
+    // Released actions stop the player on the next tick
+    game.release("move_right").tick(10);
+    assert_eq!(position(&mut game) - start, moved);
+}
+
+#[test]
+fn opposite_actions_cancel() {
+    let mut game = TestGame::new();
+    game.start(1);
+    let start = position(&mut game);
+    game.hold("move_up").hold("move_down").tick(20);
+    assert_eq!(position(&mut game), start);
+}
+
+#[test]
+fn partial_value_moves_slower() {
+    let mut game = TestGame::new();
+    game.start(1);
+    let start = position(&mut game);
+    game.hold_value("move_up", 0.5).tick(TICK_RATE);
+    let moved = position(&mut game) - start;
+    assert!((moved.y - SPEED / 2.).abs() < 0.01, "moved {moved}");
+}
+
+#[test]
+fn lower_player_is_drawn_in_front() {
+    let mut game = TestGame::new();
+    game.start(1).tick(1);
+    let depth = |game: &mut TestGame| game.single::<Transform, Player>().translation.z;
+    let start = depth(&mut game);
+    let map = game
+        .app
+        .world
+        .query_filtered::<&Transform, With<SortLayer>>()
+        .iter(&game.app.world)
+        .map(|trans| trans.translation.z)
+        .fold(f32::INFINITY, f32::min);
+    assert!(map < start);
+
+    game.hold("move_down").tick(10);
+    assert!(depth(&mut game) > start);
+    game.release("move_down").hold("move_up").tick(20);
+    assert!(depth(&mut game) < start);
+}
+
+#[test]
+fn faces_where_it_walks() {
+    let mut game = TestGame::new();
+    game.start(1).tick(1);
+    assert_eq!(game.single::<Facing, Player>().0, Vec2::NEG_X);
+    game.hold("move_up").tick(2);
+    assert_eq!(game.single::<Facing, Player>().0, Vec2::Y);
+    // Standing still keeps the last facing
+    game.release("move_up").tick(2);
+    assert_eq!(game.single::<Facing, Player>().0, Vec2::Y);
+}
+
+#[test]
+fn respawns_after_three_seconds_of_ticks() {
+    let mut game = TestGame::new();
+    game.start(1).hold("move_right").tick(10);
+    let player = game
+        .app
+        .world
+        .query_filtered::<Entity, With<Player>>()
+        .single(&game.app.world);
+    game.release("move_right");
+    game.app.world.send_event(DamageEvent {
+        target: player,
+        amount: u32::MAX,
+    });
+    game.tick(1);
+    assert!(game.app.world.get::<Dead>(player).is_some());
+
+    game.tick(3 * TICK_RATE - 1);
+    assert!(game.app.world.get::<Dead>(player).is_some());
+    game.tick(1);
+    assert!(game.app.world.get::<Dead>(player).is_none());
+    assert_eq!(position(&mut game), Vec2::ZERO);
+    let health = game.single::<Health, Player>();
+    assert_eq!(health.current, health.max);
+}
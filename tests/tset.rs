@@ -0,0 +1,440 @@
+mod common;
+
+use bevy::{asset::LoadState, prelude::*, sprite::TextureAtlasBuilderError};
+use common::TestGame;
+use miner::resources::{Frame, FrameDuration, SourceLink, TSetManager, TSetTile, Tile};
+
+const FIXTURE: &str = "test.tset.ron";
+// Tile size and columns of the `grid` source in the fixture
+const TILE: f32 = 8.;
+const COLUMNS: usize = 3;
+
+#[test]
+fn parses_tiles() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+
+    assert!(matches!(
+        tset.get_tile("single"),
+        Tile::Single(SourceLink::Texture { source }) if source == "single"
+    ));
+    assert!(matches!(
+        tset.get_tile("corner"),
+        Tile::Single(SourceLink::Atlas { source, index }) if source == "grid" && index == Vec2::new(3., 2.)
+    ));
+    assert!(matches!(
+        tset.get_tile("anim"),
+        Tile::Animated { frames, speed } if frames.len() == 2 && speed == 0.5
+    ));
+    assert!(matches!(
+        tset.get_tile("var"),
+        Tile::Variant { variants } if variants.len() == 2
+    ));
+    assert!(matches!(tset.get_tile("group"), Tile::Group(_)));
+    assert!(matches!(
+        tset.get_tile("group/inner"),
+        Tile::Single(SourceLink::Atlas { index, .. }) if index == Vec2::new(2., 2.)
+    ));
+}
+
+#[test]
+fn missing_tile_is_default() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+
+    assert!(matches!(
+        tset.get_tile("nothing/here"),
+        Tile::Single(SourceLink::Texture { source }) if source == "_@default"
+    ));
+    let (_, atlas) = tset.index_and_atlas(SourceLink::Atlas {
+        source: "grid".to_string(),
+        index: Vec2::ONE,
+    });
+    let (index, default) = tset.index_and_atlas(SourceLink::Texture {
+        source: "_@default".to_string(),
+    });
+    assert_eq!(index, 0);
+    assert_ne!(default, atlas);
+}
+
+#[test]
+fn atlas_index_math() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    let cell = |x: f32, y: f32| {
+        tset.index_and_atlas(SourceLink::Atlas {
+            source: "grid".to_string(),
+            index: Vec2::new(x, y),
+        })
+    };
+
+    // Cells are numbered from 1, row by row
+    let (first, atlas) = cell(1., 1.);
+    assert_eq!(cell(2., 1.).0, first + 1);
+    assert_eq!(cell(3., 1.).0, first + 2);
+    assert_eq!(cell(1., 2.).0, first + COLUMNS);
+    assert_eq!(cell(3., 2.).0, first + COLUMNS + 2);
+
+    // Every cell points at its part of the source image in the atlas
+    let (single, single_atlas) = tset.index_and_atlas(SourceLink::Texture {
+        source: "single".to_string(),
+    });
+    assert_eq!(single_atlas, atlas);
+    let atlas = game.atlas(&atlas);
+    assert_eq!(atlas.textures[single].size(), Vec2::splat(TILE));
+    let grid = atlas
+        .textures
+        .iter()
+        .find(|rect| rect.size() == Vec2::new(3. * TILE, 2. * TILE))
+        .expect("grid source is in the atlas");
+    for y in 0..2 {
+        for x in 0..COLUMNS {
+            let rect = atlas.textures[first + y * COLUMNS + x];
+            assert_eq!(rect.min, grid.min + Vec2::new(x as f32, y as f32) * TILE);
+            assert_eq!(rect.size(), Vec2::splat(TILE));
+        }
+    }
+}
+
+#[test]
+fn resolves_manager_tiles() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    let index = |name: &str, data: TSetTile| {
+        tset.resolve(&TSetManager::new(handle.clone(), name, data))
+            .0
+    };
+    let cell = |x: f32, y: f32| {
+        tset.index_and_atlas(SourceLink::Atlas {
+            source: "grid".to_string(),
+            index: Vec2::new(x, y),
+        })
+        .0
+    };
+
+    assert_eq!(index("group/inner", TSetTile::Single), cell(2., 2.));
+    assert_eq!(
+        index("var", TSetTile::Variant("a".to_string())),
+        cell(1., 2.)
+    );
+    // Speed 0.5 shows every frame for two ticks
+    assert_eq!(index("anim", TSetTile::Animated(0)), cell(1., 1.));
+    assert_eq!(index("anim", TSetTile::Animated(1)), cell(1., 1.));
+    assert_eq!(index("anim", TSetTile::Animated(2)), cell(2., 1.));
+    assert_eq!(index("anim", TSetTile::Animated(4)), cell(1., 1.));
+}
+
+#[test]
+fn frames_with_durations_and_flips() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    let frame = |tick| {
+        tset.frame(&TSetManager::new(
+            handle.clone(),
+            "mirrored",
+            TSetTile::Animated(tick),
+        ))
+    };
+    let cell = |frame: Frame| match frame.link {
+        SourceLink::Atlas { index, .. } => index,
+        _ => panic!("not an atlas link"),
+    };
+
+    // 3 ticks, 1 tick with the default speed, then 2 mirrored ticks
+    let cells: Vec<_> = (0..7).map(|tick| cell(frame(tick))).collect();
+    let first = Vec2::new(1., 1.);
+    let second = Vec2::new(2., 1.);
+    assert_eq!(cells, [first, first, first, second, first, first, first]);
+    let flips: Vec<_> = (0..7).map(|tick| frame(tick).flip_x).collect();
+    assert_eq!(flips, [false, false, false, false, true, true, false]);
+
+    let mirrored = frame(5);
+    assert!(!mirrored.flip_y);
+    assert_eq!(mirrored.offset, Vec2::new(2., -1.));
+    assert_eq!(frame(0).offset, Vec2::ZERO);
+}
+
+#[test]
+fn directional_tiles_follow_facing() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    let facing = |x: f32, y: f32| {
+        tset.resolve(&TSetManager::new(
+            handle.clone(),
+            "facing",
+            TSetTile::Direction(Vec2::new(x, y)),
+        ))
+        .0
+    };
+    let cell = |x: f32, y: f32| {
+        tset.index_and_atlas(SourceLink::Atlas {
+            source: "grid".to_string(),
+            index: Vec2::new(x, y),
+        })
+        .0
+    };
+    let (single, _) = tset.index_and_atlas(SourceLink::Texture {
+        source: "single".to_string(),
+    });
+
+    assert_eq!(facing(1., 0.), cell(1., 1.));
+    assert_eq!(facing(0., 0.5), cell(1., 2.));
+    assert_eq!(facing(0., -1.), cell(2., 2.));
+    // Any length works, the closest direction is used
+    assert_eq!(facing(3., 0.4), cell(1., 1.));
+    assert_eq!(facing(-1., -1.), single);
+    // Without up_right, horizontal wins the tie
+    assert_eq!(facing(1., 1.), cell(1., 1.));
+    // Facing without a length has no direction
+    let (zero, default) = tset.resolve(&TSetManager::new(
+        handle.clone(),
+        "facing",
+        TSetTile::Direction(Vec2::ZERO),
+    ));
+    assert_eq!(zero, 0);
+    assert_ne!(
+        default,
+        tset.resolve(&TSetManager::new(
+            handle.clone(),
+            "single",
+            TSetTile::Single
+        ))
+        .1
+    );
+}
+
+#[test]
+fn animated_directions_use_manager_frame() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    let mut manager = TSetManager::new(handle.clone(), "facing", TSetTile::Direction(Vec2::NEG_X));
+    let cell = |x: f32| {
+        tset.index_and_atlas(SourceLink::Atlas {
+            source: "grid".to_string(),
+            index: Vec2::new(x, 1.),
+        })
+        .0
+    };
+    assert_eq!(tset.resolve(&manager).0, cell(2.));
+    manager.update_frame(2);
+    assert_eq!(tset.resolve(&manager).0, cell(3.));
+    manager.update_frame(4);
+    assert_eq!(tset.resolve(&manager).0, cell(2.));
+}
+
+#[test]
+fn unbuilt_set_and_unknown_source_are_default() {
+    let mut game = TestGame::new();
+    let (handle, state) = game.try_load_tset(FIXTURE);
+    assert_eq!(state, LoadState::Loaded);
+    let link = SourceLink::Texture {
+        source: "single".to_string(),
+    };
+    // Loaded late, before its atlas is built
+    let (index, default) = game.tset(&handle).index_and_atlas(link.clone());
+    assert_eq!(index, 0);
+
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    let (_, atlas) = tset.index_and_atlas(link);
+    assert_ne!(atlas, default);
+    let (index, unknown) = tset.index_and_atlas(SourceLink::Texture {
+        source: "missing".to_string(),
+    });
+    assert_eq!(index, 0);
+    assert_eq!(unknown, default);
+}
+
+#[test]
+fn out_of_range_cell_is_default() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    let (_, atlas) = tset.index_and_atlas(SourceLink::Atlas {
+        source: "grid".to_string(),
+        index: Vec2::ONE,
+    });
+    for index in [Vec2::ZERO, Vec2::new(4., 1.), Vec2::new(1., 3.)] {
+        let (cell, default) = tset.index_and_atlas(SourceLink::Atlas {
+            source: "grid".to_string(),
+            index,
+        });
+        assert_eq!(cell, 0);
+        assert_ne!(default, atlas);
+    }
+}
+
+fn moved(rect: Rect, by: Vec2) -> Rect {
+    Rect::from_corners(rect.min + by, rect.max + by)
+}
+
+// Finds the rect of a whole source image by its size
+fn source_rect(game: &TestGame, atlas: &Handle<TextureAtlas>, size: Vec2) -> Rect {
+    *game
+        .atlas(atlas)
+        .textures
+        .iter()
+        .find(|rect| rect.size() == size)
+        .expect("source is in the atlas")
+}
+
+#[test]
+fn padded_zero_based_grid() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    let cell = |x: f32| {
+        tset.index_and_atlas(SourceLink::Atlas {
+            source: "padded".to_string(),
+            index: Vec2::new(x, 0.),
+        })
+    };
+    let (first, atlas) = cell(0.);
+    let (second, _) = cell(1.);
+    assert_eq!(second, first + 1);
+    let (_, default) = cell(2.);
+    assert_ne!(default, atlas);
+
+    // Offset is 1px, the second cell is after a 2px gap
+    let image = source_rect(&game, &atlas, Vec2::new(12., 6.));
+    let rects = &game.atlas(&atlas).textures;
+    assert_eq!(rects[first], moved(Rect::new(1., 1., 5., 5.), image.min));
+    assert_eq!(rects[second], moved(Rect::new(7., 1., 11., 5.), image.min));
+}
+
+#[test]
+fn named_rects() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    let (wide, atlas) = tset.resolve(&TSetManager::new(handle.clone(), "wide", TSetTile::Single));
+    let (tall, _) = tset.index_and_atlas(SourceLink::Rect {
+        source: "sheet".to_string(),
+        name: "tall".to_string(),
+    });
+    let (_, missing) = tset.index_and_atlas(SourceLink::Rect {
+        source: "sheet".to_string(),
+        name: "missing".to_string(),
+    });
+    assert_ne!(missing, atlas);
+
+    let image = source_rect(&game, &atlas, Vec2::new(20., 10.));
+    let rects = &game.atlas(&atlas).textures;
+    assert_eq!(rects[tall], moved(Rect::new(0., 0., 8., 10.), image.min));
+    assert_eq!(rects[wide], moved(Rect::new(8., 0., 20., 6.), image.min));
+}
+
+#[test]
+fn link_kind_must_match_source() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    let (_, atlas) = tset.index_and_atlas(SourceLink::Texture {
+        source: "single".to_string(),
+    });
+    let (index, default) = tset.index_and_atlas(SourceLink::Texture {
+        source: "grid".to_string(),
+    });
+    assert_eq!(index, 0);
+    assert_ne!(default, atlas);
+}
+
+#[test]
+fn sources_must_fit_their_images() {
+    let mut game = TestGame::new();
+    assert_eq!(game.try_load_tset("too_big.tset.ron").1, LoadState::Failed);
+    assert_eq!(game.try_load_tset("outside.tset.ron").1, LoadState::Failed);
+}
+
+#[test]
+fn atlas_which_does_not_fit_fails_to_build() {
+    let mut game = TestGame::new();
+    let (handle, state) = game.try_load_tset("wide.tset.ron");
+    assert_eq!(state, LoadState::Loaded);
+    assert!(matches!(
+        game.build_tset(&handle),
+        Err(TextureAtlasBuilderError::NotEnoughSpace)
+    ));
+}
+
+#[test]
+fn frame_tags_must_fit_their_sheets() {
+    let mut game = TestGame::new();
+    assert_eq!(game.try_load_tset("huge_tag.tset.ron").1, LoadState::Failed);
+    assert_eq!(
+        game.try_load_tset("reversed_tag.tset.ron").1,
+        LoadState::Failed
+    );
+}
+
+#[test]
+fn imports_aseprite_json() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    let Tile::Animated { frames, .. } = tset.get_tile("robot/walk") else {
+        panic!("walk is not animated");
+    };
+    let durations: Vec<_> = frames.iter().map(|frame| frame.duration).collect();
+    assert_eq!(durations, [Some(FrameDuration::Millis(100.)); 2]);
+
+    // Frames are in the atlas at their place in the image
+    let frame =
+        |name: &str| tset.resolve(&TSetManager::new(handle.clone(), name, TSetTile::Single));
+    let (first, atlas) = frame("robot/robot 0");
+    let (second, _) = frame("robot/robot 1");
+    let (eye, _) = frame("robot/eye");
+    let image = source_rect(&game, &atlas, Vec2::new(32., 8.));
+    let rects = &game.atlas(&atlas).textures;
+    assert_eq!(rects[first], moved(Rect::new(0., 0., 8., 8.), image.min));
+    assert_eq!(rects[second], moved(Rect::new(8., 0., 16., 8.), image.min));
+    assert_eq!(rects[eye], moved(Rect::new(10., 1., 13., 3.), image.min));
+}
+
+#[test]
+fn written_tiles_override_imported() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    assert!(matches!(
+        tset.get_tile("robot/spin"),
+        Tile::Single(SourceLink::Rect { name, .. }) if name == "robot 3.aseprite"
+    ));
+    assert!(matches!(
+        tset.get_tile("robot/idle"),
+        Tile::Single(SourceLink::Rect { name, .. }) if name == "robot 0.aseprite"
+    ));
+}
+
+#[test]
+fn millisecond_frames_follow_tick_rate() {
+    let mut game = TestGame::new();
+    let handle = game.load_tset(FIXTURE);
+    let tset = game.tset(&handle);
+    let first = tset
+        .resolve(&TSetManager::new(
+            handle.clone(),
+            "robot/robot 0",
+            TSetTile::Single,
+        ))
+        .0;
+    let mut manager = TSetManager::new(handle.clone(), "robot/walk", TSetTile::Animated(0));
+    // 100ms frames are 6 ticks long at 60 ticks per second
+    manager.update_frame(5);
+    assert_eq!(tset.resolve(&manager).0, first);
+    manager.update_frame(6);
+    assert_ne!(tset.resolve(&manager).0, first);
+    // and 3 ticks long at 30
+    manager.set_tick_length(1. / 30.);
+    manager.update_frame(3);
+    assert_ne!(tset.resolve(&manager).0, first);
+    manager.update_frame(6);
+    assert_eq!(tset.resolve(&manager).0, first);
+}